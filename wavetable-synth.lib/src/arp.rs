@@ -0,0 +1,314 @@
+// an arpeggiator, sits between note input & voice allocation. held notes go in with `press` &
+// `release` and `tick` (called once a sample) says which notes to start & stop. it doesn't
+// allocate, so embedded builds can arpeggiate too.
+
+use crate::{
+    config::SAMPLE_RATE,
+    mono::{NoteStack, NOTE_STACK_SIZE},
+    rng::Rng,
+};
+use core::fmt::Display;
+use nih_plug::prelude::Enum;
+use serde::{Deserialize, Serialize};
+
+/// the most octaves an arpeggio can span.
+pub const MAX_OCTAVES: u8 = 4;
+/// the tempo used until told otherwise, (in beats per minute).
+pub const DEFAULT_BPM: f32 = 120.0;
+
+/// the order held notes are played in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Enum, Serialize, Deserialize)]
+pub enum ArpPattern {
+    Up,
+    Down,
+    /// up then back down, without repeating the top & bottom notes.
+    UpDown,
+    Random,
+    /// the order the notes were pressed in.
+    AsPlayed,
+}
+
+impl Display for ArpPattern {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Up => write!(f, "Up"),
+            Self::Down => write!(f, "Down"),
+            Self::UpDown => write!(f, "Up-Down"),
+            Self::Random => write!(f, "Random"),
+            Self::AsPlayed => write!(f, "As Played"),
+        }
+    }
+}
+
+/// how long each arpeggiated note is, as a fraction of a whole note.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Enum, Serialize, Deserialize)]
+pub enum NoteDivision {
+    #[name = "1/4"]
+    Quarter,
+    #[name = "1/8"]
+    Eighth,
+    #[name = "1/8 T"]
+    EighthTriplet,
+    #[name = "1/16"]
+    Sixteenth,
+    #[name = "1/16 T"]
+    SixteenthTriplet,
+    #[name = "1/32"]
+    ThirtySecond,
+}
+
+impl Display for NoteDivision {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Quarter => write!(f, "1/4"),
+            Self::Eighth => write!(f, "1/8"),
+            Self::EighthTriplet => write!(f, "1/8 T"),
+            Self::Sixteenth => write!(f, "1/16"),
+            Self::SixteenthTriplet => write!(f, "1/16 T"),
+            Self::ThirtySecond => write!(f, "1/32"),
+        }
+    }
+}
+
+impl NoteDivision {
+    /// how many beats, (quarter notes) one step lasts.
+    pub fn beats(&self) -> f32 {
+        match self {
+            Self::Quarter => 1.0,
+            Self::Eighth => 0.5,
+            Self::EighthTriplet => 1.0 / 3.0,
+            Self::Sixteenth => 0.25,
+            Self::SixteenthTriplet => 1.0 / 6.0,
+            Self::ThirtySecond => 0.125,
+        }
+    }
+}
+
+/// what the arpeggiator wants done on a sample.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ArpStep {
+    /// a note to release, this always comes before `play`.
+    pub stop: Option<u8>,
+    /// a (note, velocity) to press.
+    pub play: Option<(u8, u8)>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Arpeggiator {
+    /// when off, notes go straight to the voices.
+    enabled: bool,
+    pub pattern: ArpPattern,
+    /// how many octaves the held notes are repeated over, (1 - `MAX_OCTAVES`).
+    pub octaves: u8,
+    pub rate: NoteDivision,
+    /// the tempo `rate` is synced to, in beats per minute.
+    pub bpm: f32,
+    /// how much of each step the note is held for, (0.0 - 1.0).
+    pub gate: f32,
+    /// how late every second step is, as a fraction of a step, (0.0 - 0.5).
+    pub swing: f32,
+    /// keeps playing notes after they're let go, until a new chord is played.
+    latch: bool,
+    /// the keys that are physically down.
+    pressed: NoteStack,
+    /// the notes being arpeggiated, (more than `pressed` when latched).
+    notes: NoteStack,
+    /// how many steps have been played since the arpeggio started.
+    step: usize,
+    /// samples until the next step.
+    until_step: u32,
+    /// samples until the sounding note is let go.
+    until_stop: u32,
+    /// the note currently held down on the voices.
+    sounding: Option<u8>,
+    rng: Rng,
+}
+
+impl Default for Arpeggiator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Arpeggiator {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            pattern: ArpPattern::Up,
+            octaves: 1,
+            rate: NoteDivision::Sixteenth,
+            bpm: DEFAULT_BPM,
+            gate: 0.5,
+            swing: 0.0,
+            latch: false,
+            pressed: NoteStack::new(),
+            notes: NoteStack::new(),
+            step: 0,
+            until_step: 0,
+            until_stop: 0,
+            sounding: None,
+            rng: Rng::default(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// turning it off forgets the held notes, (the one sounding is stopped on the next `tick`).
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+
+        if !enabled {
+            self.clear();
+        }
+    }
+
+    pub fn latch(&self) -> bool {
+        self.latch
+    }
+
+    /// unlatching drops any notes that aren't still held down.
+    pub fn set_latch(&mut self, latch: bool) {
+        self.latch = latch;
+
+        if !latch {
+            self.notes = self.pressed;
+        }
+    }
+
+    pub fn press(&mut self, note: u8, velocity: u8) {
+        // the first key after letting everything go starts a new latched chord.
+        if self.latch && self.pressed.is_empty() {
+            self.notes.clear();
+        }
+
+        if self.notes.is_empty() {
+            // start on the beat.
+            self.step = 0;
+            self.until_step = 0;
+        }
+
+        self.pressed.push(note, velocity);
+        self.notes.push(note, velocity);
+    }
+
+    pub fn release(&mut self, note: u8) {
+        self.pressed.remove(note);
+
+        if !self.latch {
+            self.notes.remove(note);
+        }
+    }
+
+    /// forgets every note, (the one sounding is stopped on the next `tick`).
+    pub fn clear(&mut self) {
+        self.pressed.clear();
+        self.notes.clear();
+    }
+
+    /// the note currently held down on the voices.
+    pub fn sounding(&self) -> Option<u8> {
+        self.sounding
+    }
+
+    /// moves the arpeggio on by one sample.
+    pub fn tick(&mut self) -> ArpStep {
+        let mut step = ArpStep::default();
+
+        if !self.enabled || self.notes.is_empty() {
+            step.stop = self.sounding.take();
+
+            return step;
+        }
+
+        if self.sounding.is_some() {
+            if self.until_stop == 0 {
+                step.stop = self.sounding.take();
+            } else {
+                self.until_stop -= 1;
+            }
+        }
+
+        if self.until_step > 0 {
+            self.until_step -= 1;
+
+            return step;
+        }
+
+        // a gate of 1.0 runs right up to the next note.
+        if step.stop.is_none() {
+            step.stop = self.sounding.take();
+        }
+
+        let step_len = self.rate.beats() * 60.0 / self.bpm.max(1.0) * SAMPLE_RATE as f32;
+        let swing = self.swing.clamp(0.0, 0.5) * step_len;
+        // the on-beat steps are stretched & the off-beat ones squashed so pairs keep time.
+        let len = if self.step.is_multiple_of(2) {
+            step_len + swing
+        } else {
+            step_len - swing
+        };
+        self.until_step = (len as u32).saturating_sub(1);
+        self.until_stop = ((len * self.gate.clamp(0.0, 1.0)) as u32).saturating_sub(1);
+
+        if let Some((note, velocity)) = self.next_note() {
+            self.sounding = Some(note);
+            step.play = Some((note, velocity));
+        }
+
+        self.step = self.step.wrapping_add(1);
+
+        step
+    }
+
+    /// the (note, velocity) for the current step.
+    fn next_note(&mut self) -> Option<(u8, u8)> {
+        let mut sequence = [(0, 0); NOTE_STACK_SIZE * MAX_OCTAVES as usize];
+        let held = self.notes.held();
+        let mut len = 0;
+
+        let mut ordered = [(0, 0); NOTE_STACK_SIZE];
+        ordered[..held.len()].copy_from_slice(held);
+        let ordered = &mut ordered[..held.len()];
+
+        if self.pattern != ArpPattern::AsPlayed {
+            ordered.sort_unstable_by_key(|(note, _)| *note);
+        }
+
+        for octave in 0..self.octaves.clamp(1, MAX_OCTAVES) {
+            for (note, velocity) in ordered.iter() {
+                let Some(note) = note.checked_add(octave * 12).filter(|note| *note < 128) else {
+                    continue;
+                };
+
+                sequence[len] = (note, *velocity);
+                len += 1;
+            }
+        }
+
+        let sequence = &sequence[..len];
+
+        if sequence.is_empty() {
+            return None;
+        }
+
+        let i = match self.pattern {
+            ArpPattern::Up | ArpPattern::AsPlayed => self.step % len,
+            ArpPattern::Down => len - 1 - self.step % len,
+            ArpPattern::UpDown if len == 1 => 0,
+            ArpPattern::UpDown => {
+                let i = self.step % (len * 2 - 2);
+
+                if i < len {
+                    i
+                } else {
+                    len * 2 - 2 - i
+                }
+            }
+            ArpPattern::Random => self.rng.next_u32() as usize % len,
+        };
+
+        Some(sequence[i])
+    }
+}
@@ -1,4 +1,4 @@
-use crate::config::{N_ENV, N_LFO, N_OSC};
+use crate::config::{N_ENV, N_LFO, N_OSC, N_STEP_SEQ};
 // use midi_control::MidiNote;
 use serde::{Deserialize, Serialize};
 
@@ -10,6 +10,7 @@ pub struct DataTable {
     pub osc: [f32; N_OSC],
     pub env: [f32; N_ENV],
     pub lfos: [f32; N_LFO],
+    pub step_seqs: [f32; N_STEP_SEQ],
     pub filter_1: f32,
     pub filter_2: f32,
     pub chorus: f32,
@@ -19,6 +20,14 @@ pub struct DataTable {
     pub note: Option<MidiNote>,
     // pub freq: Option<f32>,
     pub velocity: Option<u8>,
+    /// how fast the key was let go, `None` until it is.
+    pub release_velocity: Option<u8>,
+    /// where the note is compared to the voice's center key, (see `key_track`).
+    pub key_track: f32,
+    /// drawn when the note starts, (0.0 - 1.0).
+    pub random: f32,
+    /// 0.0 & 1.0 on every other note.
+    pub alternate: f32,
     pub pitch_bend: f32,
     pub mod_wheel: f32,
     pub macros: [f32; 4],
@@ -35,10 +44,18 @@ impl DataTable {
                     0.0
                 }
             }
+            ModMatrixSrc::ReleaseVelocity => self
+                .release_velocity
+                .map(|vel| vel as f32 / 127.0)
+                .unwrap_or(0.0),
+            ModMatrixSrc::KeyTrack => self.key_track,
+            ModMatrixSrc::Random => self.random,
+            ModMatrixSrc::Alternate => self.alternate,
             ModMatrixSrc::PitchWheel => self.pitch_bend,
             ModMatrixSrc::ModWheel => self.mod_wheel,
             ModMatrixSrc::Env(i) => self.env[*i],
             ModMatrixSrc::Lfo(i) => self.lfos[*i],
+            ModMatrixSrc::StepSeq(i) => self.step_seqs[*i],
             ModMatrixSrc::Macro1 => self.macros[0],
             ModMatrixSrc::Macro2 => self.macros[1],
             ModMatrixSrc::Macro3 => self.macros[2],
@@ -47,11 +64,29 @@ impl DataTable {
     }
 }
 
+/// the release velocity used when a note off doesn't say, (the MIDI spec's default).
+pub const DEFAULT_RELEASE_VELOCITY: u8 = 64;
+/// the key that key tracking is centered on by default, (middle C).
+pub const KEY_CENTER: u8 = 60;
+
+/// where `note` is compared to `center`, 0.0 at the center & +/- 1.0 about five octaves away.
+pub fn key_track(note: u8, center: u8) -> f32 {
+    ((note as f32 - center as f32) / 64.0).clamp(-1.0, 1.0)
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub enum ModMatrixSrc {
     Velocity,
+    ReleaseVelocity,
+    /// the note relative to the center key, -1.0 to 1.0 across the keyboard.
+    KeyTrack,
+    /// a new random value for each note.
+    Random,
+    /// flips between 0.0 & 1.0 on each note.
+    Alternate,
     Env(usize),
     Lfo(usize),
+    StepSeq(usize),
     Gate,
     Macro1,
     Macro2,
@@ -71,7 +106,12 @@ pub enum ModMatrixSrc {
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub enum OscParam {
     Level,
+    /// scales the fine tuning, (see `Oscillator::cents`).
     Tune,
+    FmIndex,
+    PmIndex,
+    /// pushes the phase, 1.0 => one whole cycle.
+    Phase,
     // Pan,
 }
 
@@ -116,8 +156,8 @@ pub enum LowPassParam {
     Cutoff,
     Res,
     Mix,
-    // KeyTrack,
-    // Drive,
+    KeyTrack,
+    Drive,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
@@ -139,7 +179,11 @@ pub enum ModMatrixDest {
         low_pass: LowPass,
         param: LowPassParam,
     },
-    // Effect { effect: Effect, Param: usize }
+    /// `effect` is the index into `Voice::effects`, `param` is the index from `Effect::get_param`.
+    Effect {
+        effect: usize,
+        param: usize,
+    },
     SynthVolume,
 }
 
@@ -3,5 +3,6 @@ pub const OSC_WAVE_TABLE_SIZE: usize = 1024;
 pub const LFO_WAVE_TABLE_SIZE: usize = 128;
 pub const POLYPHONY: usize = 10;
 pub const N_LFO: usize = 4;
+pub const N_STEP_SEQ: usize = 2;
 pub const N_ENV: usize = 4;
 pub const N_OSC: usize = 3;
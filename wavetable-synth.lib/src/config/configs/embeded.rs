@@ -3,5 +3,6 @@ pub const OSC_WAVE_TABLE_SIZE: usize = 256;
 pub const LFO_WAVE_TABLE_SIZE: usize = 64;
 pub const POLYPHONY: usize = 1;
 pub const N_LFO: usize = 2;
+pub const N_STEP_SEQ: usize = 1;
 pub const N_ENV: usize = 2;
 pub const N_OSC: usize = 3;
@@ -0,0 +1,204 @@
+// changes sent to an `App` from other threads. the audio thread owns the `App` outright and picks
+// commands up off a bounded lock-free queue between samples, so it never waits on a lock that a
+// MIDI or UI thread is holding. commands that own memory are handed back once they've run, so
+// it's freed on a control thread instead.
+
+use crate::{
+    common::DEFAULT_RELEASE_VELOCITY, midi::ShutdownHandle, pedal::PEDAL_DOWN, App, MidiControlled,
+};
+use anyhow::{anyhow, Result};
+use log::*;
+use midi_control::{message::SysExType, ControlEvent, KeyEvent, MidiMessage, MidiNote};
+use std::sync::{Arc, Mutex};
+
+/// how many commands can be waiting on the audio thread before `ControlHandle::send` fails.
+pub const COMMAND_QUEUE_SIZE: usize = 1024;
+
+pub enum Command {
+    Play {
+        note: MidiNote,
+        velocity: u8,
+    },
+    Stop {
+        note: MidiNote,
+        /// how fast the key was let go.
+        velocity: u8,
+    },
+    /// bends every voice, (-1.0 - 1.0).
+    PitchBend(f32),
+    /// sets the mod wheel on every voice, (0.0 - 1.0).
+    ModWheel(f32),
+    /// puts the sustain pedal down, (true) or lifts it.
+    Sustain(bool),
+    /// puts the sostenuto pedal down, (true) or lifts it.
+    Sostenuto(bool),
+    /// a MIDI Tuning Standard SysEx message, (see `App::apply_mts`).
+    MidiTuning(Vec<u8>),
+    /// runs on the audio thread, for parameter changes. anything slow (parsing, building wave
+    /// tables, etc) should be done before it's sent. it's `FnMut` so it can be handed back
+    /// afterwards with whatever it owns, (swap values out of the `App` to have them freed too).
+    Edit(Box<dyn FnMut(&mut App) + Send>),
+}
+
+impl core::fmt::Debug for Command {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Play { note, velocity } => f
+                .debug_struct("Play")
+                .field("note", note)
+                .field("velocity", velocity)
+                .finish(),
+            Self::Stop { note, velocity } => f
+                .debug_struct("Stop")
+                .field("note", note)
+                .field("velocity", velocity)
+                .finish(),
+            Self::PitchBend(bend) => f.debug_tuple("PitchBend").field(bend).finish(),
+            Self::ModWheel(mod_wheel) => f.debug_tuple("ModWheel").field(mod_wheel).finish(),
+            Self::Sustain(down) => f.debug_tuple("Sustain").field(down).finish(),
+            Self::Sostenuto(down) => f.debug_tuple("Sostenuto").field(down).finish(),
+            Self::MidiTuning(message) => f.debug_tuple("MidiTuning").field(message).finish(),
+            Self::Edit(_) => f.write_str("Edit"),
+        }
+    }
+}
+
+impl Command {
+    /// what a MIDI message should do, if it does anything.
+    pub fn from_midi(message: &MidiMessage) -> Option<Self> {
+        match *message {
+            MidiMessage::NoteOn(_channel, KeyEvent { key, value }) if value > 0 => {
+                Some(Self::Play {
+                    note: key,
+                    velocity: value,
+                })
+            }
+            // a note on with a velocity of zero is a note off, (without a release velocity).
+            MidiMessage::NoteOn(_channel, KeyEvent { key, .. }) => Some(Self::Stop {
+                note: key,
+                velocity: DEFAULT_RELEASE_VELOCITY,
+            }),
+            MidiMessage::NoteOff(_channel, KeyEvent { key, value }) => Some(Self::Stop {
+                note: key,
+                velocity: value,
+            }),
+            MidiMessage::PitchBend(_channel, lsb, msb) => {
+                let bend = ((msb as u16) << 7 | lsb as u16) as f32 / 8192.0 - 1.0;
+
+                Some(Self::PitchBend(bend))
+            }
+            MidiMessage::ControlChange(_channel, ControlEvent { control: 1, value }) => {
+                Some(Self::ModWheel(value as f32 / 127.0))
+            }
+            MidiMessage::ControlChange(_channel, ControlEvent { control: 64, value }) => {
+                Some(Self::Sustain(value >= PEDAL_DOWN))
+            }
+            MidiMessage::ControlChange(_channel, ControlEvent { control: 66, value }) => {
+                Some(Self::Sostenuto(value >= PEDAL_DOWN))
+            }
+            MidiMessage::SysEx(ref sysex) => {
+                let (universal, device, sub_ids) = match *sysex.get_type() {
+                    SysExType::NonRealTime(device, sub_ids) => (0x7E, device, sub_ids),
+                    SysExType::RealTime(device, sub_ids) => (0x7F, device, sub_ids),
+                    SysExType::Manufacturer(_) => return None,
+                };
+                // MIDI Tuning Standard messages have a sub id of 0x08.
+                let [0x08, sub_id] = sub_ids else {
+                    return None;
+                };
+                let mut message = vec![0xF0, universal, device, 0x08, sub_id];
+                message.extend_from_slice(sysex.get_data());
+
+                Some(Self::MidiTuning(message))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// sends commands to an `App` from any thread, (see `App::control_handle`). clones all talk to
+/// the same `App`.
+#[derive(Clone, Debug)]
+pub struct ControlHandle {
+    /// the lock is only ever fought over by control threads, the audio thread never touches it.
+    queues: Arc<Mutex<Queues>>,
+    shutdown: ShutdownHandle,
+}
+
+/// the control side of the queues to & from the audio thread.
+#[derive(Debug)]
+struct Queues {
+    commands: rtrb::Producer<Command>,
+    /// commands the audio thread is done with, dropped here so it doesn't free any memory.
+    spent: rtrb::Consumer<Command>,
+}
+
+/// the audio thread's side of a `ControlHandle`, (see `App::run_command`).
+pub(crate) struct CommandQueues {
+    pub commands: rtrb::Consumer<Command>,
+    pub spent: rtrb::Producer<Command>,
+}
+
+impl ControlHandle {
+    pub(crate) fn new(shutdown: ShutdownHandle) -> (Self, CommandQueues) {
+        let (commands, commands_rx) = rtrb::RingBuffer::new(COMMAND_QUEUE_SIZE);
+        let (spent_tx, spent) = rtrb::RingBuffer::new(COMMAND_QUEUE_SIZE);
+        let handle = Self {
+            queues: Arc::new(Mutex::new(Queues { commands, spent })),
+            shutdown,
+        };
+        let queues = CommandQueues {
+            commands: commands_rx,
+            spent: spent_tx,
+        };
+
+        (handle, queues)
+    }
+
+    /// queues `command` for the audio thread, and drops any commands it's handed back. fails if
+    /// the queue is full, (the audio thread isn't running or has fallen behind).
+    ///
+    /// all the clones of a handle share one `Mutex`, so two threads sending at once wait on each
+    /// other, (but never on the audio thread).
+    pub fn send(&self, command: Command) -> Result<()> {
+        let mut queues = self.queues.lock().unwrap_or_else(|e| e.into_inner());
+
+        while queues.spent.pop().is_ok() {}
+
+        queues
+            .commands
+            .push(command)
+            .map_err(|_| anyhow!("the command queue is full"))
+    }
+
+    pub fn play(&self, note: MidiNote, velocity: u8) -> Result<()> {
+        self.send(Command::Play { note, velocity })
+    }
+
+    pub fn stop(&self, note: MidiNote) -> Result<()> {
+        self.send(Command::Stop {
+            note,
+            velocity: DEFAULT_RELEASE_VELOCITY,
+        })
+    }
+
+    /// runs `edit` on the audio thread, (see `Command::Edit`).
+    pub fn edit(&self, edit: impl FnMut(&mut App) + Send + 'static) -> Result<()> {
+        self.send(Command::Edit(Box::new(edit)))
+    }
+
+    /// the shutdown handle of the `App` this controls.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+}
+
+impl MidiControlled for ControlHandle {
+    fn midi_input(&mut self, message: &MidiMessage) {
+        if let Some(command) = Command::from_midi(message) {
+            if let Err(e) = self.send(command) {
+                warn!("dropped a MIDI message: {e}");
+            }
+        }
+    }
+}
@@ -1,7 +1,7 @@
-use super::{Effect, EffectParam};
-use crate::{config::SAMPLE_RATE, tanh, SampleGen};
+use super::{Effect, EffectParam, EffectParamInfo};
+use crate::{calculate_modulation, config::SAMPLE_RATE, smooth::Smoothed, tanh, SampleGen};
 use core::fmt::Display;
-use strum::EnumIter;
+use strum::{EnumIter, IntoEnumIterator};
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, EnumIter)]
 pub enum ChorusParam {
@@ -11,10 +11,25 @@ pub enum ChorusParam {
 
 impl Display for ChorusParam {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        match *self {
-            Self::Volume => write!(f, "Vol."),
-            Self::Speed => write!(f, "Speed"),
-        }
+        write!(f, "{}", self.name())
+    }
+}
+
+impl TryFrom<usize> for ChorusParam {
+    type Error = usize;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        Ok(match value {
+            _ if value == Self::Volume as usize => Self::Volume,
+            _ if value == Self::Speed as usize => Self::Speed,
+            _ => return Err(value),
+        })
+    }
+}
+
+impl From<ChorusParam> for usize {
+    fn from(value: ChorusParam) -> Self {
+        value as usize
     }
 }
 
@@ -32,7 +47,21 @@ impl Display for ChorusParam {
 //     }
 // }
 
-impl EffectParam for ChorusParam {}
+impl EffectParam for ChorusParam {
+    fn name(&self) -> &'static str {
+        match *self {
+            Self::Volume => "Vol.",
+            Self::Speed => "Speed",
+        }
+    }
+
+    fn range(&self) -> (f32, f32) {
+        match *self {
+            Self::Volume => (0.0, 1.0),
+            Self::Speed => (0.0, 1.0),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct Chorus {
@@ -42,8 +71,12 @@ pub struct Chorus {
     pub get_i: usize,
     pub step: usize,
     pub volume: f32,
+    /// follows `volume` so changing it doesn't click.
+    smooth_volume: Smoothed,
     pub speed: f32,
     pub input: f32,
+    volume_mod: f32,
+    speed_mod: f32,
     // lfo_input: LfoInput,
 }
 
@@ -62,8 +95,11 @@ impl Chorus {
             get_i: 0,
             step: (SAMPLE_RATE as f32 * (0.25 * 0.5)) as usize,
             volume: 0.75,
+            smooth_volume: Smoothed::new(0.75),
             speed: 0.25,
             input: 0.0,
+            volume_mod: 0.0,
+            speed_mod: 0.0,
             // lfo_input: LfoInput::default(),
         }
     }
@@ -85,6 +121,12 @@ impl Chorus {
     pub fn set_speed(&mut self, speed: f32) {
         // info!("speed: {}", speed);
         self.speed = speed;
+        self.calc_step();
+    }
+
+    fn calc_step(&mut self) {
+        let (min, max) = ChorusParam::Speed.range();
+        let speed = calculate_modulation(self.speed, self.speed_mod).clamp(min, max);
         // self.step = (SAMPLE_RATE as f32 * (speed * 0.05)) as usize;
         self.step = (SAMPLE_RATE as f32 * (speed * 0.5)) as usize;
         // info!("step:  {}", self.step);
@@ -93,6 +135,11 @@ impl Chorus {
     pub fn set_volume(&mut self, volume: f32) {
         self.volume = volume;
     }
+
+    /// sets how long (in seconds) a change to `volume` takes to fade in.
+    pub fn set_volume_ramp(&mut self, ramp: f32) {
+        self.smooth_volume.set_ramp(ramp);
+    }
 }
 
 impl SampleGen for Chorus {
@@ -105,27 +152,64 @@ impl SampleGen for Chorus {
 
 impl Effect for Chorus {
     fn take_input(&mut self, value: f32) {
-        self.input = value * self.volume;
+        self.smooth_volume.set(self.volume);
+        let volume = self.smooth_volume.tick();
+        self.input = value * calculate_modulation(volume, self.volume_mod);
         self.buff[self.instert_i] = self.input;
         self.instert_i += 1;
         self.instert_i %= self.size;
     }
 
-    // fn get_param_list(&self) -> Vec<String> {
-    //     ChorusParam::iter()
-    //         .map(|param| format!("{param}"))
-    //         .collect()
-    // }
-    //
-    // fn get_params(&self) -> crate::HashMap<String, f32> {
-    //     let map = HashMap::default();
-    //
-    //     // TODO: Write this
-    //
-    //     map
-    // }
-    //
-    // fn set_param(&mut self, _param: &str, _to: f32) {
-    //     // TODO: Write this
-    // }
+    fn n_params(&self) -> usize {
+        ChorusParam::iter().count()
+    }
+
+    fn get_param(&self, param: usize) -> Option<EffectParamInfo> {
+        let param = ChorusParam::try_from(param).ok()?;
+
+        let value = match param {
+            ChorusParam::Volume => self.volume,
+            ChorusParam::Speed => self.speed,
+        };
+
+        Some(EffectParamInfo::new(param, value))
+    }
+
+    fn set_param(&mut self, param: usize, to: f32) {
+        let Ok(param) = ChorusParam::try_from(param) else {
+            return;
+        };
+        let (min, max) = param.range();
+        let to = to.clamp(min, max);
+
+        match param {
+            ChorusParam::Volume => self.set_volume(to),
+            ChorusParam::Speed => self.set_speed(to),
+        }
+    }
+
+    fn modulate_param(&mut self, param: usize, by: f32) {
+        let Ok(param) = ChorusParam::try_from(param) else {
+            return;
+        };
+
+        match param {
+            ChorusParam::Volume => self.volume_mod = by,
+            ChorusParam::Speed => {
+                if self.speed_mod != by {
+                    self.speed_mod = by;
+                    self.calc_step();
+                }
+            }
+        }
+    }
+
+    fn reset_mods(&mut self) {
+        self.volume_mod = 0.0;
+
+        if self.speed_mod != 0.0 {
+            self.speed_mod = 0.0;
+            self.calc_step();
+        }
+    }
 }
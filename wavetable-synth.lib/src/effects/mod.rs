@@ -8,17 +8,53 @@ use strum::EnumIter;
 pub mod chorus;
 // pub mod reverb;
 
-pub trait EffectParam: Debug + Clone + Display /* + TryFrom<f32> */ {}
+pub trait EffectParam: Debug + Clone + Copy + Display + TryFrom<usize> + Into<usize> {
+    /// the name of the parameter as it should be shown to the user.
+    fn name(&self) -> &'static str;
+    /// the (min, max) values this parameter can be set to.
+    fn range(&self) -> (f32, f32);
+}
+
+/// describes one parameter of an effect. (its index, name, range, and current value.)
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct EffectParamInfo {
+    /// the index used to address this param in `Effect::set_param` & `ModMatrixDest::Effect`
+    pub param: usize,
+    pub name: &'static str,
+    pub min: f32,
+    pub max: f32,
+    pub value: f32,
+}
+
+impl EffectParamInfo {
+    pub fn new<P: EffectParam>(param: P, value: f32) -> Self {
+        let (min, max) = param.range();
+
+        Self {
+            param: param.into(),
+            name: param.name(),
+            min,
+            max,
+            value,
+        }
+    }
+}
 
 #[enum_dispatch(EffectsModule)]
 pub trait Effect: Debug + SampleGen + Send {
     // type Param: EffectParam;
 
     fn take_input(&mut self, value: f32);
-    // fn get_param_list(&self) -> Vec<String>;
-    // fn get_params(&self) -> HashMap<String, f32>;
-    // fn get_params(&self) -> HashMap<String, f32>;
-    // fn set_param(&mut self, param: &str, to: f32);
+    /// the number of parameters this effect has.
+    fn n_params(&self) -> usize;
+    /// returns the info for param number `param`, or None if there is no such param.
+    fn get_param(&self, param: usize) -> Option<EffectParamInfo>;
+    /// sets param number `param`, `to` gets clamped to the params range.
+    fn set_param(&mut self, param: usize, to: f32);
+    /// modulates param number `param` by `by`, used by the mod matrix.
+    fn modulate_param(&mut self, param: usize, by: f32);
+    /// clears any applied modulation.
+    fn reset_mods(&mut self);
 }
 
 #[enum_dispatch]
@@ -28,6 +64,29 @@ pub enum EffectsModule {
     Chorus(Chorus),
 }
 
+impl EffectsModule {
+    /// lists the names of all the params of this effect, in index order.
+    pub fn get_param_list(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.get_params().map(|info| info.name)
+    }
+
+    /// lists all params of this effect along with their ranges and current values.
+    pub fn get_params(&self) -> impl Iterator<Item = EffectParamInfo> + '_ {
+        (0..self.n_params()).filter_map(|param| self.get_param(param))
+    }
+
+    /// finds a param by name and sets it. returns false if no param has that name.
+    pub fn set_param_by_name(&mut self, name: &str, to: f32) -> bool {
+        let Some(param) = self.get_params().find(|info| info.name == name) else {
+            return false;
+        };
+
+        self.set_param(param.param, to);
+
+        true
+    }
+}
+
 // impl From<EffectType> for EffectsModule {
 //     fn from(value: EffectType) -> Self {
 //         match value {
@@ -52,12 +52,29 @@ pub type OscWaveTable = Vec<f32>;
 #[cfg(feature = "desktop")]
 pub type LfoWaveTable = Vec<f32>;
 
+pub mod arp;
 pub mod common;
 pub mod config;
+#[cfg(feature = "desktop")]
+pub mod control;
 pub mod effects;
 pub mod lfo;
+#[cfg(feature = "desktop")]
+pub mod midi;
+pub mod mono;
+#[cfg(feature = "desktop")]
+pub mod patch;
+pub mod pedal;
+#[cfg(feature = "desktop")]
+pub mod render;
+pub mod rng;
+pub mod sink;
+pub mod smooth;
+pub mod step_seq;
 pub mod synth_engines;
+pub mod tuning;
 pub mod voice;
+pub mod wavetable;
 
 #[cfg(feature = "desktop")]
 pub trait MidiControlled {
@@ -79,18 +96,39 @@ pub trait ModulationDest {
 
 #[cfg(feature = "desktop")]
 #[allow(dead_code)]
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct App {
     /// used to coordinate exits from run_midi function
-    exit: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    shutdown: midi::ShutdownHandle,
     /// describes what modulates what.
     pub mod_matrix: ModMatrix,
     /// used for routung cc messages
     pub midi_table: [Option<ModMatrixDest>; 256],
-    /// the sound producers
-    pub voices: std::sync::Arc<[std::sync::RwLock<Voice>]>,
+    /// the sound producers, owned by whichever thread makes the audio.
+    pub voices: Box<[Voice]>,
+    /// poly, or mono/legato on the first voice. (see `set_voice_mode`)
+    voice_mode: mono::VoiceMode,
+    /// which held note plays when not in poly mode.
+    pub note_priority: mono::NotePriority,
+    /// the notes held down, only kept when not in poly mode.
+    held: mono::NoteStack,
+    /// plays held notes one at a time when enabled, (see `set_arp_enabled`).
+    pub arp: arp::Arpeggiator,
+    /// the sustain & sostenuto pedals.
+    pedals: pedal::Pedals,
+    /// what the alternate mod source is set to on the next note.
+    alternate: bool,
+    /// the pitch each note plays, every voice has a copy.
+    tuning: tuning::Tuning,
     /// all pass filter to avoid clipping
     allpass: biquad::DirectForm1<f32>,
+    /// commands from other threads, run between samples.
+    commands: rtrb::Consumer<control::Command>,
+    /// hands commands that own memory back to the control threads once they've run, (see
+    /// `ControlHandle::send`).
+    spent: rtrb::Producer<control::Command>,
+    /// given out to other threads so they can send commands.
+    control: control::ControlHandle,
 }
 
 #[cfg(feature = "desktop")]
@@ -98,7 +136,6 @@ impl Default for App {
     fn default() -> Self {
         use crate::config::SAMPLE_RATE;
         use biquad::*;
-        use std::sync::{Arc, RwLock};
 
         let mut overtones = [1.0; N_OVERTONES];
 
@@ -107,7 +144,7 @@ impl Default for App {
         let wave_table = build_sine_table(&overtones);
 
         let voices = (0..POLYPHONY)
-            .map(|_| RwLock::new(Voice::new(wave_table.clone())))
+            .map(|_| Voice::new(wave_table.clone()))
             .collect();
 
         // Cutoff and sampling frequencies
@@ -117,45 +154,40 @@ impl Default for App {
             Coefficients::<f32>::from_params(Type::AllPass, fs, f0, Q_BUTTERWORTH_F32).unwrap();
         let allpass = DirectForm1::<f32>::new(coeffs);
 
-        Self {
-            exit: Arc::new(false.into()),
+        let shutdown = midi::ShutdownHandle::default();
+        let (control, queues) = control::ControlHandle::new(shutdown.clone());
+
+        let mut app = Self {
+            shutdown,
             mod_matrix: [None; 256],
             midi_table: [None; 256],
             voices,
+            voice_mode: mono::VoiceMode::Poly,
+            note_priority: mono::NotePriority::Last,
+            held: mono::NoteStack::new(),
+            arp: arp::Arpeggiator::new(),
+            pedals: pedal::Pedals::new(),
+            alternate: false,
+            tuning: tuning::Tuning::default(),
             allpass,
-        }
+            commands: queues.commands,
+            spent: queues.spent,
+            control,
+        };
+        // so the voices don't all draw the same random values.
+        app.seed_random(0);
+
+        app
     }
 }
 
 #[cfg(feature = "desktop")]
-#[allow(unused_variables)]
 impl MidiControlled for App {
     fn midi_input(&mut self, message: &midi_control::MidiMessage) {
-        use midi_control::{KeyEvent, MidiMessage};
-
         // TODO: if note, add midi note to the data table
         // TODO: if cc, route based on learned midi table
-        match *message {
-            MidiMessage::NoteOn(_channel, KeyEvent { key, value }) => {
-                for voice in self.voices.iter() {
-                    if let Ok(mut voice) = voice.write() {
-                        if voice.playing.is_none() {
-                            voice.press(key, value);
-                            break;
-                        }
-                    }
-                }
-            }
-            MidiMessage::NoteOff(_channel, KeyEvent { key, value }) => {
-                for voice in self.voices.iter() {
-                    if let Ok(mut voice) = voice.write() {
-                        if voice.playing.is_some_and(|note| note == key) {
-                            voice.release();
-                        }
-                    }
-                }
-            }
-            _ => {}
+        if let Some(command) = control::Command::from_midi(message) {
+            self.run_command(command);
         }
     }
 }
@@ -165,10 +197,24 @@ impl SampleGen for App {
     fn get_sample(&mut self) -> f32 {
         use biquad::Biquad;
 
+        while let Ok(command) = self.commands.pop() {
+            self.run_command(command);
+        }
+
+        let step = self.arp.tick();
+
+        if let Some(note) = step.stop {
+            self.release_note(note);
+        }
+
+        if let Some((note, velocity)) = step.play {
+            self.press_note(note, velocity);
+        }
+
         let sample: f32 = self
             .voices
-            .iter()
-            .map(|voice| voice.write().unwrap().get_sample(&self.mod_matrix))
+            .iter_mut()
+            .map(|voice| voice.get_sample(&self.mod_matrix))
             .sum();
 
         // AllPass filter
@@ -183,96 +229,264 @@ impl SampleGen for App {
 #[cfg(feature = "desktop")]
 impl App {
     pub fn play(&mut self, note: midi_control::MidiNote, velocity: u8) {
-        for voice in self.voices.iter() {
-            if let Ok(mut voice) = voice.write() {
-                if voice.playing.is_none() {
-                    voice.press(note, velocity);
-                    break;
-                }
-            }
+        self.pedals.press(note);
+
+        if self.arp.enabled() {
+            self.arp.press(note, velocity);
+        } else {
+            self.press_note(note, velocity);
         }
     }
 
+    /// lets go of the key for `note`, the note keeps going if a pedal is holding it.
     pub fn stop(&mut self, note: midi_control::MidiNote) {
-        for voice in self.voices.iter() {
-            if let Ok(mut voice) = voice.write() {
-                if voice.playing.is_some_and(|n| n == note) {
-                    voice.release();
+        self.stop_with_velocity(note, common::DEFAULT_RELEASE_VELOCITY);
+    }
+
+    /// like `stop` but with how fast the key was let go, (for the release velocity mod source).
+    pub fn stop_with_velocity(&mut self, note: midi_control::MidiNote, velocity: u8) {
+        self.voices
+            .iter_mut()
+            .filter(|voice| voice.playing == Some(note))
+            .for_each(|voice| voice.set_release_velocity(velocity));
+
+        if self.pedals.release(note) {
+            self.end_note(note);
+        }
+    }
+
+    /// puts the sustain pedal down or lifts it, (CC64).
+    pub fn set_sustain(&mut self, down: bool) {
+        let stop = self.pedals.set_sustain(down);
+        stop.iter().for_each(|note| self.end_note(note));
+    }
+
+    /// puts the sostenuto pedal down or lifts it, (CC66).
+    pub fn set_sostenuto(&mut self, down: bool) {
+        let stop = self.pedals.set_sostenuto(down);
+        stop.iter().for_each(|note| self.end_note(note));
+    }
+
+    /// stops `note` once the pedals are done with it.
+    fn end_note(&mut self, note: u8) {
+        if self.arp.enabled() {
+            self.arp.release(note);
+        } else {
+            self.release_note(note);
+        }
+    }
+
+    /// turns the arpeggiator on or off. notes that were playing are let go so none get stuck.
+    pub fn set_arp_enabled(&mut self, enabled: bool) {
+        if enabled == self.arp.enabled() {
+            return;
+        }
+
+        self.arp.set_enabled(enabled);
+
+        if enabled {
+            self.release_all();
+        }
+    }
+
+    pub fn tuning(&self) -> &tuning::Tuning {
+        &self.tuning
+    }
+
+    /// retunes every voice, (notes that are sounding move to their new pitches).
+    pub fn set_tuning(&mut self, tuning: tuning::Tuning) {
+        self.tuning = tuning;
+        self.voices
+            .iter_mut()
+            .for_each(|voice| voice.set_tuning(tuning));
+    }
+
+    /// retunes notes from a MIDI Tuning Standard SysEx message, (see `tuning::Tuning::apply_mts`).
+    pub fn apply_mts(&mut self, message: &[u8]) -> Result<()> {
+        let mut tuning = self.tuning;
+        tuning.apply_mts(message)?;
+        self.set_tuning(tuning);
+
+        Ok(())
+    }
+
+    /// reseeds the random mod source on every voice, so renders can be repeated exactly.
+    pub fn seed_random(&mut self, seed: u32) {
+        self.voices
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, voice)| voice.seed_random(seed.wrapping_add(i as u32)));
+    }
+
+    /// sets the tempo the arpeggiator & step sequencers are synced to, in beats per minute.
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.arp.bpm = bpm;
+        self.voices
+            .iter_mut()
+            .for_each(|voice| voice.set_tempo(bpm));
+    }
+
+    /// gives `note` to a voice, (skipping the arpeggiator).
+    fn press_note(&mut self, note: u8, velocity: u8) {
+        use mono::VoiceMode;
+
+        match self.voice_mode {
+            VoiceMode::Poly => {
+                // a note that's still sounding, (held by a pedal) is played again on its own voice.
+                let i = self
+                    .voices
+                    .iter()
+                    .position(|voice| voice.playing == Some(note))
+                    .or_else(|| self.voices.iter().position(|voice| voice.playing.is_none()));
+
+                let Some(i) = i else {
+                    return;
+                };
+
+                self.voices[i].press(note, velocity);
+                self.voices[i].set_alternate(self.alternate);
+            }
+            mode => {
+                self.held.press(
+                    &mut self.voices[0],
+                    note,
+                    velocity,
+                    mode == VoiceMode::Legato,
+                    self.note_priority,
+                );
+                self.voices[0].set_alternate(self.alternate);
+            }
+        }
+
+        self.alternate = !self.alternate;
+    }
+
+    pub fn voice_mode(&self) -> mono::VoiceMode {
+        self.voice_mode
+    }
+
+    /// switches between poly & mono/legato, releasing every voice so no notes get stuck.
+    pub fn set_voice_mode(&mut self, mode: mono::VoiceMode) {
+        if mode == self.voice_mode {
+            return;
+        }
+
+        self.voice_mode = mode;
+        self.release_all();
+    }
+
+    fn release_all(&mut self) {
+        self.held.clear();
+        self.voices
+            .iter_mut()
+            .filter(|voice| voice.playing.is_some())
+            .for_each(|voice| voice.release());
+    }
+
+    /// shutting this down makes `run_midi` return.
+    pub fn shutdown_handle(&self) -> midi::ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// a handle other threads can use to control this `App` while it's making audio.
+    pub fn control_handle(&self) -> control::ControlHandle {
+        self.control.clone()
+    }
+
+    pub fn run_command(&mut self, mut command: control::Command) {
+        use control::Command;
+
+        match command {
+            Command::Play { note, velocity } => self.play(note, velocity),
+            Command::Stop { note, velocity } => self.stop_with_velocity(note, velocity),
+            Command::Sustain(down) => self.set_sustain(down),
+            Command::Sostenuto(down) => self.set_sostenuto(down),
+            Command::PitchBend(bend) => self.voices.iter_mut().for_each(|voice| voice.bend(bend)),
+            Command::ModWheel(mod_wheel) => self
+                .voices
+                .iter_mut()
+                .for_each(|voice| voice.set_mod_wheel(mod_wheel)),
+            Command::MidiTuning(ref message) => {
+                if let Err(e) = self.apply_mts(message) {
+                    warn!("ignored a MIDI tuning message: {e}");
                 }
+
+                self.hand_back(command);
+            }
+            Command::Edit(ref mut edit) => {
+                edit(self);
+                self.hand_back(command);
             }
         }
     }
+
+    /// sends a finished command back to be dropped off the audio thread. if the control threads
+    /// haven't kept up it gets dropped here anyway.
+    fn hand_back(&mut self, command: control::Command) {
+        let _ = self.spent.push(command);
+    }
+
+    /// lets go of `note` on the voices, (skipping the arpeggiator).
+    fn release_note(&mut self, note: u8) {
+        use mono::VoiceMode;
+
+        match self.voice_mode {
+            VoiceMode::Poly => self
+                .voices
+                .iter_mut()
+                .filter(|voice| voice.playing.is_some_and(|n| n == note))
+                .for_each(|voice| voice.release()),
+            mode => self.held.release(
+                &mut self.voices[0],
+                note,
+                mode == VoiceMode::Legato,
+                self.note_priority,
+            ),
+        }
+    }
 }
 
 pub fn midi_to_freq(midi_note: i16) -> f32 {
-    let exp = (f32::from(midi_note) + 36.376_316) / 12.0;
+    note_to_freq(f32::from(midi_note))
+}
+
+/// like `midi_to_freq` but takes fractional notes, (for glides & the like).
+pub fn note_to_freq(note: f32) -> f32 {
+    let exp = (note - tuning::A4_NOTE as f32) / 12.0;
+
+    tuning::A4_FREQ * pow(2.0, exp)
+}
 
-    pow(2.0, exp)
+/// the (fractional) 12-TET midi note for `freq`, the opposite of `note_to_freq`.
+pub fn freq_to_note(freq: f32) -> f32 {
+    tuning::A4_NOTE as f32 + 12.0 * log2(freq / tuning::A4_FREQ)
 }
 
 pub fn calculate_modulation(base: f32, amt: f32) -> f32 {
     base + base * amt
 }
 
+/// sends MIDI from every input port to the `App` that `control` belongs to, until that `App`'s
+/// `shutdown_handle` is shut down. (see `midi::MidiInputs` to pick which ports & channels are used)
 #[cfg(feature = "desktop")]
-pub fn run_midi(
-    synth: std::sync::Arc<std::sync::RwLock<App>>, /* , exit: Arc<AtomicBool> */
-) -> Result<()> {
-    use midir::{Ignore, MidiInput, PortInfoError};
-
-    let mut registered_ports = HashMap::default();
-    let exit = {
-        let app = synth.read().unwrap();
-        app.exit.clone()
-    };
-
-    while !exit.load(std::sync::atomic::Ordering::Relaxed) {
-        let mut midi_in = MidiInput::new("midir reading input")?;
-        midi_in.ignore(Ignore::None);
-
-        // Get an input port (read from console if multiple are available)
-        let in_ports = midi_in.ports();
-        let port_names: Vec<std::result::Result<String, PortInfoError>> = in_ports
-            .iter()
-            .map(|port| midi_in.port_name(port))
-            .collect();
-        registered_ports.retain(|k: &String, _| port_names.contains(&Ok(k.clone())));
+pub fn run_midi(control: control::ControlHandle) -> Result<()> {
+    let shutdown = control.shutdown_handle();
 
-        for in_port in in_ports.iter() {
-            let Ok(port_name) = midi_in.port_name(in_port) else {
-                continue;
-            };
+    midi::MidiInputs::default().run(&control, &shutdown)
+}
 
-            if registered_ports.contains_key(&port_name) {
-                continue;
-            }
+/// the names of every MIDI input port that's currently available.
+#[cfg(feature = "desktop")]
+pub fn list_midi_ports() -> Result<Vec<String>> {
+    use midir::MidiInput;
 
-            info!("port {port_name}");
-            let mut midi_in = MidiInput::new("midir reading input")?;
-            midi_in.ignore(Ignore::None);
-            let synth = synth.clone();
-
-            registered_ports.insert(
-                port_name,
-                midi_in.connect(
-                    in_port,
-                    "midir-read-input",
-                    move |_stamp, message, _| {
-                        let message = midi_control::MidiMessage::from(message);
-
-                        // do midi stuff
-                        // synth.lock().unwrap().midi_input(&message);
-                        if let Ok(mut synth) = synth.write() {
-                            synth.midi_input(&message);
-                        }
-                    },
-                    (),
-                ),
-            );
-        }
-    }
+    let midi_in = MidiInput::new("midir listing ports")?;
 
-    Ok(())
+    Ok(midi_in
+        .ports()
+        .iter()
+        .filter_map(|port| midi_in.port_name(port).ok())
+        .collect())
 }
 
 #[cfg(feature = "desktop")]
@@ -333,6 +547,20 @@ fn pow(base: f32, exp: f32) -> f32 {
     powf(base, exp)
 }
 
+#[cfg(feature = "std")]
+#[inline]
+fn log2(x: f32) -> f32 {
+    x.log2()
+}
+
+#[cfg(feature = "embeded")]
+#[inline]
+fn log2(x: f32) -> f32 {
+    use libm::log2f;
+
+    log2f(x)
+}
+
 #[cfg(feature = "std")]
 #[inline]
 fn tanh(x: f32) -> f32 {
@@ -373,6 +601,20 @@ fn exp(x: f32) -> f32 {
     expf(x)
 }
 
+#[cfg(feature = "std")]
+#[inline]
+fn tan(x: f32) -> f32 {
+    x.tan()
+}
+
+#[cfg(feature = "embeded")]
+#[inline]
+fn tan(x: f32) -> f32 {
+    use libm::tanf;
+
+    tanf(x)
+}
+
 #[cfg(feature = "std")]
 #[inline]
 fn sin(x: f64) -> f64 {
@@ -0,0 +1,167 @@
+use crate::{control::ControlHandle, HashMap, MidiControlled};
+use anyhow::Result;
+use log::*;
+use midir::{Ignore, MidiInput, MidiInputConnection, MidiInputPort};
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+/// how often to look for MIDI devices being plugged in or unplugged by default.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// a channel mask that listens to all 16 channels.
+pub const ALL_CHANNELS: u16 = u16::MAX;
+
+/// stops `MidiInputs::run`, (and `run_midi`). every clone stops the same thing.
+#[derive(Clone, Debug, Default)]
+pub struct ShutdownHandle {
+    state: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        let (done, wake) = &*self.state;
+        *done.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        wake.notify_all();
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        *self.state.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// sleeps for `timeout` or until `shutdown` is called. returns true if it was shut down.
+    fn wait(&self, timeout: Duration) -> bool {
+        let (done, wake) = &*self.state;
+        let done = done.lock().unwrap_or_else(|e| e.into_inner());
+        let (done, _) = wake
+            .wait_timeout_while(done, timeout, |done| !*done)
+            .unwrap_or_else(|e| e.into_inner());
+
+        *done
+    }
+}
+
+/// connects the MIDI inputs that pass its filters to an `App`, (through its `ControlHandle`) and
+/// keeps connecting to devices as they're plugged in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MidiInputs {
+    /// only use ports whose name contains one of these, (ignoring case). empty => every port.
+    pub include: Vec<String>,
+    /// never use ports whose name contains one of these, even if they're included.
+    pub exclude: Vec<String>,
+    /// which channels to listen to, bit 0 => channel 1. system messages always get through.
+    pub channels: u16,
+    /// how often to look for new & unplugged devices.
+    pub poll_interval: Duration,
+}
+
+impl Default for MidiInputs {
+    fn default() -> Self {
+        Self {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            channels: ALL_CHANNELS,
+            poll_interval: POLL_INTERVAL,
+        }
+    }
+}
+
+impl MidiInputs {
+    /// whether the port called `name` passes the include & exclude filters.
+    pub fn accepts_port(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        let matches = |pattern: &String| name.contains(&pattern.to_lowercase());
+
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+
+    /// whether a raw MIDI `message` passes the channel filter.
+    pub fn accepts_message(&self, message: &[u8]) -> bool {
+        accepts_channel(self.channels, message)
+    }
+
+    /// sends MIDI from every accepted port to `control` until `shutdown` is shut down, then closes
+    /// the ports.
+    pub fn run(&self, control: &ControlHandle, shutdown: &ShutdownHandle) -> Result<()> {
+        let watcher = MidiInput::new("wavetable-synth watcher")?;
+        // keyed by port id, so devices with the same name each get their own connection.
+        let mut connections: HashMap<String, (String, MidiInputConnection<()>)> =
+            HashMap::default();
+
+        loop {
+            let ports: Vec<(MidiInputPort, String, String)> = watcher
+                .ports()
+                .into_iter()
+                .filter_map(|port| {
+                    let name = watcher.port_name(&port).ok()?;
+                    let id = port.id();
+
+                    Some((port, id, name))
+                })
+                .collect();
+
+            connections.retain(|id, (name, _)| {
+                let plugged_in = ports.iter().any(|(_, port_id, _)| port_id == id);
+
+                if !plugged_in {
+                    info!("MIDI port {name} was unplugged");
+                }
+
+                plugged_in
+            });
+
+            for (port, id, name) in ports {
+                if connections.contains_key(&id) || !self.accepts_port(&name) {
+                    continue;
+                }
+
+                let mut midi_in = MidiInput::new("wavetable-synth input")?;
+                midi_in.ignore(Ignore::None);
+                let mut control = control.clone();
+                let channels = self.channels;
+
+                let connection = midi_in.connect(
+                    &port,
+                    "wavetable-synth-input",
+                    move |_stamp, message, _| {
+                        if !accepts_channel(channels, message) {
+                            return;
+                        }
+
+                        control.midi_input(&midi_control::MidiMessage::from(message));
+                    },
+                    (),
+                );
+
+                match connection {
+                    Ok(connection) => {
+                        info!("listening to MIDI port {name}");
+                        connections.insert(id, (name, connection));
+                    }
+                    Err(e) => warn!("connecting to MIDI port {name} failed: {e}"),
+                }
+            }
+
+            if shutdown.wait(self.poll_interval) {
+                break;
+            }
+        }
+
+        // dropping a connection closes its port.
+        for (name, connection) in connections.into_values() {
+            connection.close();
+            info!("closed MIDI port {name}");
+        }
+
+        Ok(())
+    }
+}
+
+fn accepts_channel(channels: u16, message: &[u8]) -> bool {
+    match message.first() {
+        // channel voice messages, (note on/off, CC, pitch bend, etc).
+        Some(status) if (0x80..0xF0).contains(status) => channels & (1 << (status & 0x0F)) != 0,
+        _ => true,
+    }
+}
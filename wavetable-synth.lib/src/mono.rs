@@ -0,0 +1,179 @@
+// playing one voice monophonically. a `NoteStack` remembers the notes being held so letting go of
+// one goes back to whichever should sound next, it doesn't need an `App` so embedded builds, (with
+// a single voice) can use it too.
+
+use crate::voice::Voice;
+use core::fmt::Display;
+use nih_plug::prelude::Enum;
+use serde::{Deserialize, Serialize};
+
+/// how many held notes a `NoteStack` remembers. past this the oldest ones are forgotten.
+pub const NOTE_STACK_SIZE: usize = 16;
+
+/// how notes are given out to voices.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Enum, Serialize, Deserialize)]
+pub enum VoiceMode {
+    /// every note gets its own voice.
+    Poly,
+    /// one voice, every new note restarts the envelopes.
+    Mono,
+    /// one voice, the envelopes only restart when no other note was held.
+    Legato,
+}
+
+impl Display for VoiceMode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Poly => write!(f, "Poly"),
+            Self::Mono => write!(f, "Mono"),
+            Self::Legato => write!(f, "Legato"),
+        }
+    }
+}
+
+/// which of the held notes a mono voice plays.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Enum, Serialize, Deserialize)]
+pub enum NotePriority {
+    /// the most recently played.
+    Last,
+    /// the lowest.
+    Low,
+    /// the highest.
+    High,
+}
+
+impl Display for NotePriority {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Last => write!(f, "Last"),
+            Self::Low => write!(f, "Low"),
+            Self::High => write!(f, "High"),
+        }
+    }
+}
+
+/// the notes being held down, (note, velocity) oldest first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NoteStack {
+    notes: [(u8, u8); NOTE_STACK_SIZE],
+    len: usize,
+}
+
+impl Default for NoteStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NoteStack {
+    pub fn new() -> Self {
+        Self {
+            notes: [(0, 0); NOTE_STACK_SIZE],
+            len: 0,
+        }
+    }
+
+    /// adds `note` as the newest held note, (moving it to the top if it was already held).
+    pub fn push(&mut self, note: u8, velocity: u8) {
+        self.remove(note);
+
+        if self.len == NOTE_STACK_SIZE {
+            self.notes.copy_within(1.., 0);
+            self.len -= 1;
+        }
+
+        self.notes[self.len] = (note, velocity);
+        self.len += 1;
+    }
+
+    /// returns false if `note` wasn't held.
+    pub fn remove(&mut self, note: u8) -> bool {
+        let Some(i) = self.held().iter().position(|(n, _)| *n == note) else {
+            return false;
+        };
+
+        self.notes.copy_within(i + 1..self.len, i);
+        self.len -= 1;
+
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// the held (note, velocity) pairs, oldest first.
+    pub fn held(&self) -> &[(u8, u8)] {
+        &self.notes[..self.len]
+    }
+
+    /// the (note, velocity) that should be sounding.
+    pub fn current(&self, priority: NotePriority) -> Option<(u8, u8)> {
+        let held = self.held().iter().copied();
+
+        match priority {
+            NotePriority::Last => held.last(),
+            NotePriority::Low => held.min_by_key(|(note, _)| *note),
+            NotePriority::High => held.max_by_key(|(note, _)| *note),
+        }
+    }
+
+    /// holds `note` down & plays whichever held note `priority` picks on `voice`. `legato` keeps
+    /// the envelopes going while other notes are held, (see `VoiceMode`).
+    pub fn press(
+        &mut self,
+        voice: &mut Voice,
+        note: u8,
+        velocity: u8,
+        legato: bool,
+        priority: NotePriority,
+    ) {
+        let overlapping = !self.is_empty();
+        self.push(note, velocity);
+
+        let Some((note, velocity)) = self.current(priority) else {
+            return;
+        };
+
+        if overlapping && voice.playing == Some(note) {
+            // a note the priority skips over.
+            return;
+        }
+
+        if !overlapping || voice.playing.is_none() {
+            voice.press(note, velocity);
+        } else if legato {
+            voice.legato(note);
+        } else {
+            voice.press_overlapping(note, velocity);
+        }
+    }
+
+    /// lets go of `note`, going back to the next held note or releasing `voice` if there are none.
+    pub fn release(&mut self, voice: &mut Voice, note: u8, legato: bool, priority: NotePriority) {
+        // a note off for a note that isn't held, (a stray one or from before a mode switch).
+        if !self.remove(note) {
+            return;
+        }
+
+        match self.current(priority) {
+            None => {
+                if voice.playing.is_some() {
+                    voice.release();
+                }
+            }
+            // the voice was stopped some other way or is already on the right note.
+            Some((note, _)) if voice.playing.is_none_or(|playing| playing == note) => {}
+            Some((note, _)) if legato => voice.legato(note),
+            Some((note, velocity)) => voice.press_overlapping(note, velocity),
+        }
+    }
+}
@@ -0,0 +1,453 @@
+use crate::{
+    arp::{ArpPattern, Arpeggiator, NoteDivision},
+    common::{ModMatrixDest, ModMatrixItem, ModMatrixSrc},
+    config::{N_ENV, N_LFO, N_OSC, N_STEP_SEQ},
+    mono::{NotePriority, VoiceMode},
+    step_seq::{Step, MAX_STEPS},
+    synth_engines::{
+        synth::{
+            noise::NoiseColor,
+            osc::{CrossMod, OscTarget, PhaseMode},
+            sub_osc::{SubOctave, SubShape},
+        },
+        synth_common::{
+            env::{ATTACK, DECAY, RELEASE, SUSTAIN},
+            filter::{FilterModel, FilterResponse, FilterRouting},
+            glide::{GlideMode, GlideRate},
+        },
+    },
+    voice::Voice,
+    wavetable::formula::Formula,
+    App, OscWaveTable, MOD_MATRIX_SIZE,
+};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OscPatch {
+    pub enabled: bool,
+    /// a formula to draw the wave table with, (see `wavetable::formula`). `None` keeps the
+    /// current table.
+    pub wave_table: Option<String>,
+    pub level: f32,
+    /// fine tuning in cents.
+    #[serde(default)]
+    pub cents: f32,
+    /// fine tuning in semitones, only read from patches saved before `cents` replaced it.
+    #[serde(default, skip_serializing)]
+    pub detune: Option<f32>,
+    pub offset: i16,
+    #[serde(default)]
+    pub phase_mode: PhaseMode,
+    /// the start phase when `phase_mode` is `Reset`, (0.0 - 1.0).
+    #[serde(default)]
+    pub phase: f32,
+    pub target: OscTarget,
+    pub filter_sends: [f32; 2],
+    pub cross_mod: CrossMod,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SubPatch {
+    pub enabled: bool,
+    pub level: f32,
+    pub octave: SubOctave,
+    pub shape: SubShape,
+    pub target: OscTarget,
+    pub filter_sends: [f32; 2],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NoisePatch {
+    pub enabled: bool,
+    pub level: f32,
+    pub color: NoiseColor,
+    pub target: OscTarget,
+    pub filter_sends: [f32; 2],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EnvPatch {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LfoPatch {
+    pub freq: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StepSeqPatch {
+    pub rate: NoteDivision,
+    /// the sequence loops after the last of these.
+    pub steps: Vec<Step>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FilterPatch {
+    pub model: FilterModel,
+    pub response: FilterResponse,
+    pub cutoff: f32,
+    pub resonance: f32,
+    pub mix: f32,
+    pub key_track: f32,
+    pub drive: f32,
+    pub env: usize,
+    pub env_amt: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GlidePatch {
+    pub mode: GlideMode,
+    pub rate: GlideRate,
+    pub time: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArpPatch {
+    pub enabled: bool,
+    pub pattern: ArpPattern,
+    pub octaves: u8,
+    pub rate: NoteDivision,
+    pub bpm: f32,
+    pub gate: f32,
+    pub swing: f32,
+    pub latch: bool,
+}
+
+impl Default for ArpPatch {
+    fn default() -> Self {
+        Self::from_arp(&Arpeggiator::new())
+    }
+}
+
+impl ArpPatch {
+    pub fn from_arp(arp: &Arpeggiator) -> Self {
+        Self {
+            enabled: arp.enabled(),
+            pattern: arp.pattern,
+            octaves: arp.octaves,
+            rate: arp.rate,
+            bpm: arp.bpm,
+            gate: arp.gate,
+            swing: arp.swing,
+            latch: arp.latch(),
+        }
+    }
+}
+
+/// everything needed to recreate a sound, saved as JSON. anything left out of a patch file is
+/// taken from the default sound.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Patch {
+    pub oscs: Vec<OscPatch>,
+    pub sub: SubPatch,
+    pub noise: NoisePatch,
+    pub envs: Vec<EnvPatch>,
+    pub lfos: Vec<LfoPatch>,
+    pub step_seqs: Vec<StepSeqPatch>,
+    pub filters: [FilterPatch; 2],
+    pub filter_routing: FilterRouting,
+    pub level: f32,
+    /// the note the key track mod source is centered on.
+    pub key_center: u8,
+    pub voice_mode: VoiceMode,
+    pub note_priority: NotePriority,
+    pub glide: GlidePatch,
+    pub arp: ArpPatch,
+    pub mod_matrix: Vec<ModMatrixItem>,
+}
+
+impl Default for Patch {
+    fn default() -> Self {
+        Self::from_app(&App::default())
+    }
+}
+
+impl Patch {
+    /// takes a snapshot of the sound `app` is set up to make.
+    pub fn from_app(app: &App) -> Self {
+        let mut patch = Self::from_voice(&app.voices[0]);
+        patch.mod_matrix = app.mod_matrix.iter().flatten().copied().collect();
+        patch.voice_mode = app.voice_mode();
+        patch.note_priority = app.note_priority;
+        patch.arp = ArpPatch::from_arp(&app.arp);
+
+        patch
+    }
+
+    /// takes a snapshot of how `voice` is set up. (the mod matrix, voice mode, & arpeggiator live
+    /// in `App` so they're left at their defaults)
+    pub fn from_voice(voice: &Voice) -> Self {
+        let oscs = voice
+            .oscs
+            .iter()
+            .map(|(osc, enabled)| OscPatch {
+                enabled: *enabled,
+                wave_table: None,
+                level: osc.level,
+                cents: osc.cents,
+                detune: None,
+                offset: osc.offset,
+                phase_mode: osc.phase_mode,
+                phase: osc.phase,
+                target: osc.target,
+                filter_sends: osc.filter_sends,
+                cross_mod: osc.cross_mod,
+            })
+            .collect();
+        let (sub, sub_enabled) = &voice.sub;
+        let (noise, noise_enabled) = &voice.noise;
+        let envs = voice
+            .envs
+            .iter()
+            .map(|env| EnvPatch {
+                attack: env.base_params[ATTACK],
+                decay: env.base_params[DECAY],
+                sustain: env.base_params[SUSTAIN],
+                release: env.base_params[RELEASE],
+            })
+            .collect();
+        let lfos = voice
+            .lfos
+            .iter()
+            .map(|lfo| LfoPatch { freq: lfo.freq })
+            .collect();
+        let step_seqs = voice
+            .step_seqs
+            .iter()
+            .map(|seq| StepSeqPatch {
+                rate: seq.rate,
+                steps: seq.steps[..seq.length.clamp(1, MAX_STEPS)].to_vec(),
+            })
+            .collect();
+        let filters = voice.filters.map(|filter| FilterPatch {
+            model: filter.model,
+            response: filter.response,
+            cutoff: filter.cutoff,
+            resonance: filter.resonance,
+            mix: filter.mix,
+            key_track: filter.key_track,
+            drive: filter.drive,
+            env: filter.env,
+            env_amt: filter.env_amt,
+        });
+
+        Self {
+            oscs,
+            sub: SubPatch {
+                enabled: *sub_enabled,
+                level: sub.level,
+                octave: sub.octave,
+                shape: sub.shape,
+                target: sub.target,
+                filter_sends: sub.filter_sends,
+            },
+            noise: NoisePatch {
+                enabled: *noise_enabled,
+                level: noise.level,
+                color: noise.color,
+                target: noise.target,
+                filter_sends: noise.filter_sends,
+            },
+            envs,
+            lfos,
+            step_seqs,
+            filters,
+            filter_routing: voice.filter_routing,
+            level: voice.level,
+            key_center: voice.key_center,
+            voice_mode: VoiceMode::Poly,
+            note_priority: NotePriority::Last,
+            glide: GlidePatch {
+                mode: voice.glide.mode,
+                rate: voice.glide.rate,
+                time: voice.glide.time,
+            },
+            arp: ArpPatch::default(),
+            mod_matrix: Vec::new(),
+        }
+    }
+
+    /// sets every voice in `app` up to make this sound & replaces its mod matrix.
+    pub fn apply(&self, app: &mut App) -> Result<()> {
+        if self.mod_matrix.len() > app.mod_matrix.len() {
+            bail!(
+                "the patch has {} mod matrix entries, only {} fit",
+                self.mod_matrix.len(),
+                app.mod_matrix.len()
+            );
+        }
+
+        self.check_indices()?;
+
+        // parse the formulas once up front so a bad one doesn't leave the voices half set up.
+        let wave_tables = self
+            .oscs
+            .iter()
+            .map(|osc| {
+                osc.wave_table
+                    .as_deref()
+                    .map(|formula| Formula::parse(formula).map(|f| f.build_table()))
+                    .transpose()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for voice in app.voices.iter_mut() {
+            self.apply_to_voice(voice, &wave_tables);
+        }
+
+        app.set_voice_mode(self.voice_mode);
+        app.note_priority = self.note_priority;
+        app.set_arp_enabled(self.arp.enabled);
+        app.arp.pattern = self.arp.pattern;
+        app.arp.octaves = self.arp.octaves;
+        app.arp.rate = self.arp.rate;
+        app.arp.bpm = self.arp.bpm;
+        app.arp.gate = self.arp.gate;
+        app.arp.swing = self.arp.swing;
+        app.arp.set_latch(self.arp.latch);
+        app.mod_matrix = [None; MOD_MATRIX_SIZE];
+        self.mod_matrix
+            .iter()
+            .zip(app.mod_matrix.iter_mut())
+            .for_each(|(item, slot)| *slot = Some(*item));
+
+        Ok(())
+    }
+
+    /// checks that every env, lfo, step sequencer, & osc the patch points at exists, so a bad
+    /// patch is an error here rather than a panic on the audio thread.
+    fn check_indices(&self) -> Result<()> {
+        for (i, item) in self.mod_matrix.iter().enumerate() {
+            let src = match item.src {
+                ModMatrixSrc::Env(env) => Some(("env", env, N_ENV)),
+                ModMatrixSrc::Lfo(lfo) => Some(("lfo", lfo, N_LFO)),
+                ModMatrixSrc::StepSeq(seq) => Some(("step sequencer", seq, N_STEP_SEQ)),
+                _ => None,
+            };
+            let dest = match item.dest {
+                ModMatrixDest::ModMatrixEntryModAmt(_) => {
+                    bail!("mod matrix entry {i} modulates a mod amount, which isn't supported yet")
+                }
+                ModMatrixDest::Osc { osc, .. } => Some(("osc", osc, N_OSC)),
+                ModMatrixDest::Env { env, .. } => Some(("env", env, N_ENV)),
+                ModMatrixDest::Lfo { lfo, .. } => Some(("lfo", lfo, N_LFO)),
+                _ => None,
+            };
+
+            for (what, index, count) in src.into_iter().chain(dest) {
+                if index >= count {
+                    bail!("mod matrix entry {i} uses {what} {index}, there are only {count}");
+                }
+            }
+        }
+
+        for (i, filter) in self.filters.iter().enumerate() {
+            if filter.env >= N_ENV {
+                bail!(
+                    "filter {i} follows env {}, there are only {N_ENV}",
+                    filter.env
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_to_voice(&self, voice: &mut Voice, wave_tables: &[Option<OscWaveTable>]) {
+        for (((osc, enabled), patch), wave_table) in
+            voice.oscs.iter_mut().zip(&self.oscs).zip(wave_tables)
+        {
+            *enabled = patch.enabled;
+            osc.level = patch.level;
+            osc.cents = patch
+                .detune
+                .map_or(patch.cents, |semitones| semitones * 100.0);
+            osc.offset = patch.offset;
+            osc.phase_mode = patch.phase_mode;
+            osc.phase = patch.phase.clamp(0.0, 1.0);
+            osc.target = patch.target;
+            osc.filter_sends = patch.filter_sends;
+            osc.cross_mod = patch.cross_mod;
+
+            if let Some(wave_table) = wave_table {
+                osc.wave_table = wave_table.clone();
+            }
+        }
+
+        let (sub, sub_enabled) = &mut voice.sub;
+        *sub_enabled = self.sub.enabled;
+        sub.level = self.sub.level;
+        sub.octave = self.sub.octave;
+        sub.shape = self.sub.shape;
+        sub.target = self.sub.target;
+        sub.filter_sends = self.sub.filter_sends;
+
+        let (noise, noise_enabled) = &mut voice.noise;
+        *noise_enabled = self.noise.enabled;
+        noise.level = self.noise.level;
+        noise.color = self.noise.color;
+        noise.target = self.noise.target;
+        noise.filter_sends = self.noise.filter_sends;
+
+        for (env, patch) in voice.envs.iter_mut().zip(&self.envs) {
+            // sustain first, decay & release are worked out from it.
+            env.set_sus(patch.sustain);
+            env.set_atk(patch.attack);
+            env.set_decay(patch.decay);
+            env.set_release(patch.release);
+        }
+
+        for (lfo, patch) in voice.lfos.iter_mut().zip(&self.lfos) {
+            lfo.set_frequency(patch.freq);
+        }
+
+        for (seq, patch) in voice.step_seqs.iter_mut().zip(&self.step_seqs) {
+            let len = patch.steps.len().min(MAX_STEPS);
+            seq.rate = patch.rate;
+            seq.steps[..len].copy_from_slice(&patch.steps[..len]);
+            seq.length = len.max(1);
+        }
+
+        for (filter, patch) in voice.filters.iter_mut().zip(&self.filters) {
+            filter.set_model(patch.model);
+            filter.set_response(patch.response);
+            filter.set_cutoff(patch.cutoff);
+            filter.set_resonace(patch.resonance);
+            filter.mix = patch.mix;
+            filter.set_key_track(patch.key_track);
+            filter.set_drive(patch.drive);
+            filter.env = patch.env;
+            filter.env_amt = patch.env_amt;
+        }
+
+        voice.filter_routing = self.filter_routing;
+        voice.level = self.level;
+        voice.key_center = self.key_center.min(127);
+        voice.glide.mode = self.glide.mode;
+        voice.glide.rate = self.glide.rate;
+        voice.glide.time = self.glide.time;
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, self.to_json()?)?;
+
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
@@ -0,0 +1,120 @@
+// the sustain (CC64) & sostenuto (CC66) pedals. `Pedals` sits in front of voice allocation and
+// decides when letting go of a key should actually stop its note.
+
+/// the pedal counts as down at or above this CC value.
+pub const PEDAL_DOWN: u8 = 64;
+
+/// a set of midi notes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NoteSet(u128);
+
+impl NoteSet {
+    pub fn insert(&mut self, note: u8) {
+        self.0 |= Self::bit(note);
+    }
+
+    pub fn remove(&mut self, note: u8) {
+        self.0 &= !Self::bit(note);
+    }
+
+    pub fn contains(&self, note: u8) -> bool {
+        self.0 & Self::bit(note) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// the notes in the set, lowest first.
+    pub fn iter(&self) -> impl Iterator<Item = u8> {
+        let set = self.0;
+
+        (0..128).filter(move |note| set & (1 << note) != 0)
+    }
+
+    fn bit(note: u8) -> u128 {
+        1 << (note & 0x7F)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Pedals {
+    sustain: bool,
+    sostenuto: bool,
+    /// the keys that are down.
+    keys: NoteSet,
+    /// notes let go while the sustain pedal was down, they stop when it lifts.
+    sustained: NoteSet,
+    /// notes whose keys were down when the sostenuto pedal went down, they keep going until it
+    /// lifts.
+    sostenuto_notes: NoteSet,
+}
+
+impl Pedals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sustain(&self) -> bool {
+        self.sustain
+    }
+
+    pub fn sostenuto(&self) -> bool {
+        self.sostenuto
+    }
+
+    /// a key went down. if its note was still ringing from a pedal, the key takes it over.
+    pub fn press(&mut self, note: u8) {
+        self.keys.insert(note);
+        self.sustained.remove(note);
+    }
+
+    /// a key came up, returns whether its note should stop now.
+    pub fn release(&mut self, note: u8) -> bool {
+        self.keys.remove(note);
+
+        if self.sustain {
+            self.sustained.insert(note);
+
+            return false;
+        }
+
+        !self.sostenuto_notes.contains(note)
+    }
+
+    /// returns the notes to stop, (if the pedal was lifted).
+    pub fn set_sustain(&mut self, down: bool) -> NoteSet {
+        self.sustain = down;
+
+        if down {
+            return NoteSet::default();
+        }
+
+        let mut stop = core::mem::take(&mut self.sustained);
+        // the sostenuto pedal is still holding these.
+        stop.0 &= !self.sostenuto_notes.0;
+
+        stop
+    }
+
+    /// returns the notes to stop, (if the pedal was lifted).
+    pub fn set_sostenuto(&mut self, down: bool) -> NoteSet {
+        // only the notes held when it first goes down are caught.
+        if down == self.sostenuto {
+            return NoteSet::default();
+        }
+
+        self.sostenuto = down;
+
+        if down {
+            self.sostenuto_notes = self.keys;
+
+            return NoteSet::default();
+        }
+
+        let held = core::mem::take(&mut self.sostenuto_notes);
+
+        // notes whose keys are still down, or that the sustain pedal holds, keep going.
+        NoteSet(held.0 & !self.keys.0 & !self.sustained.0)
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{
+    config::SAMPLE_RATE,
+    sink::{AudioSink, BufferSink, WavSink, BLOCK_SIZE},
+    App, MidiControlled, SampleGen,
+};
+use anyhow::Result;
+use midly::{
+    live::{LiveEvent, SystemCommon},
+    num::u7,
+    MetaMessage, Smf, Timing, TrackEventKind,
+};
+use std::path::Path;
+
+/// the tempo a MIDI file plays at until it says otherwise, in microseconds per beat. (120 bpm)
+const DEFAULT_TEMPO: f64 = 500_000.0;
+
+/// a MIDI message and the sample its played on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TimedEvent<'a> {
+    sample: usize,
+    event: LiveEvent<'a>,
+}
+
+/// plays the standard MIDI file in `smf` through `app` and returns the audio. notes, CCs, &
+/// pitch bends land on the exact sample they're timed for. `tail` is how many seconds to keep
+/// rendering after the last event, so releases & effects can ring out.
+pub fn render_midi(app: &mut App, smf: &[u8], tail: f32) -> Result<Vec<f32>> {
+    let mut sink = BufferSink::default();
+    render_midi_to(app, smf, tail, &mut sink)?;
+
+    Ok(sink.samples)
+}
+
+/// same as `render_midi` but streams the audio into `sink`. (`sink` isn't finished)
+pub fn render_midi_to(
+    app: &mut App,
+    smf: &[u8],
+    tail: f32,
+    sink: &mut impl AudioSink,
+) -> Result<()> {
+    let smf = Smf::parse(smf)?;
+    let events = timed_events(&smf);
+
+    // events are played before the sample they're timed for, so render that sample too, or the
+    // events at the end are dropped when there's no tail.
+    let end = events.last().map(|event| event.sample + 1).unwrap_or(0);
+    let n_samples = end + (tail.max(0.0) * SAMPLE_RATE as f32) as usize;
+    let mut events = events.iter().peekable();
+    let mut bytes = Vec::with_capacity(3);
+    let mut block = [0.0; BLOCK_SIZE];
+
+    for start in (0..n_samples).step_by(BLOCK_SIZE) {
+        let block = &mut block[..BLOCK_SIZE.min(n_samples - start)];
+
+        for (i, sample) in (start..).zip(block.iter_mut()) {
+            while let Some(timed) = events.next_if(|timed| timed.sample <= i) {
+                bytes.clear();
+                timed.event.write_std(&mut bytes)?;
+                app.midi_input(&midi_control::MidiMessage::from(bytes.as_slice()));
+            }
+
+            *sample = app.get_sample();
+        }
+
+        sink.write(block)?;
+    }
+
+    Ok(())
+}
+
+/// renders the MIDI file at `midi_path` (see `render_midi`) and writes it to `wav_path`.
+pub fn render_midi_file(
+    app: &mut App,
+    midi_path: impl AsRef<Path>,
+    wav_path: impl AsRef<Path>,
+    tail: f32,
+) -> Result<()> {
+    let smf = std::fs::read(midi_path)?;
+    let mut sink = WavSink::create(wav_path)?;
+    render_midi_to(app, &smf, tail, &mut sink)?;
+
+    sink.finish()
+}
+
+/// writes mono 32 bit float samples to a WAV file.
+pub fn write_wav(path: impl AsRef<Path>, samples: &[f32]) -> Result<()> {
+    let mut sink = WavSink::create(path)?;
+    sink.write(samples)?;
+
+    sink.finish()
+}
+
+/// merges every track into one list of MIDI events, sorted by the sample they play on.
+fn timed_events<'a>(smf: &Smf<'a>) -> Vec<TimedEvent<'a>> {
+    let mut events: Vec<(u64, TrackEventKind<'a>)> = smf
+        .tracks
+        .iter()
+        .flat_map(|track| {
+            track.iter().scan(0_u64, |tick, event| {
+                *tick += event.delta.as_int() as u64;
+
+                Some((*tick, event.kind))
+            })
+        })
+        .collect();
+    // stable, so events on the same tick keep the order they were written in.
+    events.sort_by_key(|(tick, _)| *tick);
+
+    let mut timed = Vec::with_capacity(events.len());
+    let mut tempo = DEFAULT_TEMPO;
+    let mut last_tick = 0;
+    let mut seconds = 0.0;
+
+    for (tick, kind) in events {
+        let seconds_per_tick = match smf.header.timing {
+            Timing::Metrical(ticks_per_beat) => {
+                tempo / 1_000_000.0 / ticks_per_beat.as_int().max(1) as f64
+            }
+            Timing::Timecode(fps, ticks_per_frame) => {
+                1.0 / (fps.as_f32() as f64 * ticks_per_frame.max(1) as f64)
+            }
+        };
+        seconds += (tick - last_tick) as f64 * seconds_per_tick;
+        last_tick = tick;
+
+        match kind {
+            TrackEventKind::Meta(MetaMessage::Tempo(us_per_beat)) => {
+                tempo = us_per_beat.as_int() as f64;
+            }
+            TrackEventKind::Midi { channel, message } => timed.push(TimedEvent {
+                sample: (seconds * SAMPLE_RATE as f64).round() as usize,
+                event: LiveEvent::Midi { channel, message },
+            }),
+            // for tuning changes, (the 0xF7 at the end is added back when it's written).
+            TrackEventKind::SysEx(data) => timed.push(TimedEvent {
+                sample: (seconds * SAMPLE_RATE as f64).round() as usize,
+                event: LiveEvent::Common(SystemCommon::SysEx(u7::slice_from_int(data))),
+            }),
+            _ => {}
+        }
+    }
+
+    timed
+}
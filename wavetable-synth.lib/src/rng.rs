@@ -0,0 +1,42 @@
+/// a tiny xorshift random number generator, good enough for audio & it works without std.
+#[derive(Clone, Copy, Debug)]
+pub struct Rng {
+    state: u32,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(0x9E37_79B9)
+    }
+}
+
+impl Rng {
+    /// makes a new generator, the same seed always gives the same numbers.
+    pub fn new(seed: u32) -> Self {
+        // xorshift gets stuck on zero.
+        Self {
+            state: if seed == 0 { 0x9E37_79B9 } else { seed },
+        }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+
+        x
+    }
+
+    /// a random number in `0.0..1.0`.
+    pub fn next_f32(&mut self) -> f32 {
+        // the top 24 bits fit in an f32 exactly.
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// a random number in `-1.0..1.0`.
+    pub fn next_bipolar(&mut self) -> f32 {
+        self.next_f32() * 2.0 - 1.0
+    }
+}
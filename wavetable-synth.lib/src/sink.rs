@@ -0,0 +1,207 @@
+// places to send audio to. an `AudioSink` takes blocks of mono samples, the desktop build comes
+// with sinks for WAV files & memory, a runner that pulls audio from an `App` into any sink, and
+// (with the `cpal` feature) a sink that plays through a sound card.
+
+use anyhow::Result;
+
+/// how many samples the runner pulls from an `App` at a time.
+pub const BLOCK_SIZE: usize = 256;
+
+pub trait AudioSink {
+    /// takes a block of mono samples, sinks that play in real time block until there's room.
+    fn write(&mut self, block: &[f32]) -> Result<()>;
+    /// flushes anything the sink is holding on to. nothing should be written after this.
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// throws audio away, (for benchmarks & running headless).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn write(&mut self, _block: &[f32]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// keeps everything written to it in memory.
+#[cfg(feature = "desktop")]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BufferSink {
+    pub samples: Vec<f32>,
+}
+
+#[cfg(feature = "desktop")]
+impl AudioSink for BufferSink {
+    fn write(&mut self, block: &[f32]) -> Result<()> {
+        self.samples.extend_from_slice(block);
+
+        Ok(())
+    }
+}
+
+/// writes a mono 32 bit float WAV file. the file isn't valid until `finish` is called.
+#[cfg(feature = "desktop")]
+pub struct WavSink {
+    writer: Option<hound::WavWriter<std::io::BufWriter<std::fs::File>>>,
+}
+
+#[cfg(feature = "desktop")]
+impl WavSink {
+    pub fn create(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: crate::config::SAMPLE_RATE,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+
+        Ok(Self {
+            writer: Some(hound::WavWriter::create(path, spec)?),
+        })
+    }
+}
+
+#[cfg(feature = "desktop")]
+impl AudioSink for WavSink {
+    fn write(&mut self, block: &[f32]) -> Result<()> {
+        let Some(writer) = self.writer.as_mut() else {
+            anyhow::bail!("the WAV file was already finished");
+        };
+
+        for sample in block {
+            writer.write_sample(*sample)?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if let Some(writer) = self.writer.take() {
+            writer.finalize()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// plays audio through the default output device. `write` blocks once `latency` samples are
+/// queued up, so a runner writing to it runs in real time.
+#[cfg(feature = "cpal")]
+pub struct DeviceSink {
+    /// lock-free, so the device's callback never waits on `write`.
+    queue: rtrb::Producer<f32>,
+    /// audio stops when this is dropped.
+    _stream: cpal::Stream,
+}
+
+#[cfg(feature = "cpal")]
+impl DeviceSink {
+    pub fn new(latency: usize) -> Result<Self> {
+        use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+        let Some(device) = cpal::default_host().default_output_device() else {
+            anyhow::bail!("there's no audio output device");
+        };
+        // plenty of devices can't do mono, so the mono signal goes to every channel.
+        let channels = device.default_output_config()?.channels();
+        let config = cpal::StreamConfig {
+            channels,
+            sample_rate: cpal::SampleRate(crate::config::SAMPLE_RATE),
+            buffer_size: cpal::BufferSize::Default,
+        };
+        let (queue, mut samples) = rtrb::RingBuffer::new(latency.max(BLOCK_SIZE));
+
+        let stream = device.build_output_stream(
+            &config,
+            move |data: &mut [f32], _| {
+                let frames = data.len() / channels as usize;
+                let n = samples.slots().min(frames);
+                let mut queued = samples.read_chunk(n).into_iter().flatten();
+
+                for frame in data.chunks_mut(channels as usize) {
+                    // play silence rather than block if the runner falls behind.
+                    frame.fill(queued.next().unwrap_or(0.0));
+                }
+            },
+            |e| log::error!("audio output failed: {e}"),
+            None,
+        )?;
+        stream.play()?;
+
+        Ok(Self {
+            queue,
+            _stream: stream,
+        })
+    }
+}
+
+#[cfg(feature = "cpal")]
+impl AudioSink for DeviceSink {
+    fn write(&mut self, mut block: &[f32]) -> Result<()> {
+        while !block.is_empty() {
+            let n = self.queue.slots().min(block.len());
+
+            if n == 0 {
+                if self.queue.is_abandoned() {
+                    anyhow::bail!("the audio device stopped");
+                }
+
+                // waits for the device to play some of what's queued.
+                std::thread::sleep(std::time::Duration::from_millis(1));
+                continue;
+            }
+
+            self.queue
+                .write_chunk_uninit(n)?
+                .fill_from_iter(block[..n].iter().copied());
+            block = &block[n..];
+        }
+
+        Ok(())
+    }
+}
+
+/// pulls `n_samples` from `app` into `sink`, a block at a time.
+#[cfg(feature = "desktop")]
+pub fn run_for(app: &mut crate::App, sink: &mut impl AudioSink, n_samples: usize) -> Result<()> {
+    use crate::SampleGen;
+
+    let mut block = [0.0; BLOCK_SIZE];
+    let mut left = n_samples;
+
+    while left > 0 {
+        let block = &mut block[..left.min(BLOCK_SIZE)];
+        block
+            .iter_mut()
+            .for_each(|sample| *sample = app.get_sample());
+        sink.write(block)?;
+        left -= block.len();
+    }
+
+    Ok(())
+}
+
+/// pulls audio from `app` into `sink` until `shutdown` is shut down, then finishes the sink. other
+/// threads control `app` through its `control_handle` while this runs.
+#[cfg(feature = "desktop")]
+pub fn run_until(
+    app: &mut crate::App,
+    sink: &mut impl AudioSink,
+    shutdown: &crate::midi::ShutdownHandle,
+) -> Result<()> {
+    use crate::SampleGen;
+
+    let mut block = [0.0; BLOCK_SIZE];
+
+    while !shutdown.is_shutdown() {
+        block
+            .iter_mut()
+            .for_each(|sample| *sample = app.get_sample());
+        sink.write(&block)?;
+    }
+
+    sink.finish()
+}
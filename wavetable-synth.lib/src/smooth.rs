@@ -0,0 +1,106 @@
+use crate::config::SAMPLE_RATE;
+
+/// how long a `Smoothed` value takes to reach a new target by default, in seconds.
+pub const DEFAULT_RAMP: f32 = 0.01;
+
+/// a value that ramps to its target over a set time instead of jumping, so changing it doesn't
+/// click or zipper.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Smoothed {
+    current: f32,
+    target: f32,
+    step: f32,
+    steps_left: u32,
+    /// how many samples a ramp takes.
+    ramp_len: u32,
+}
+
+impl Default for Smoothed {
+    fn default() -> Self {
+        Self::new(0.0)
+    }
+}
+
+impl Smoothed {
+    pub fn new(value: f32) -> Self {
+        Self::with_ramp(value, DEFAULT_RAMP)
+    }
+
+    /// `ramp` is in seconds.
+    pub fn with_ramp(value: f32, ramp: f32) -> Self {
+        let mut smoothed = Self {
+            current: value,
+            target: value,
+            step: 0.0,
+            steps_left: 0,
+            ramp_len: 0,
+        };
+        smoothed.set_ramp(ramp);
+
+        smoothed
+    }
+
+    /// sets how long (in seconds) it takes to reach a new target, 0.0 turns smoothing off. a ramp
+    /// that's already running keeps its speed.
+    pub fn set_ramp(&mut self, ramp: f32) {
+        self.ramp_len = (ramp.max(0.0) * SAMPLE_RATE as f32) as u32;
+    }
+
+    /// how long it takes to reach a new target, in seconds.
+    pub fn ramp(&self) -> f32 {
+        self.ramp_len as f32 / SAMPLE_RATE as f32
+    }
+
+    /// starts ramping towards `target`. setting the same target again does nothing, so this can be
+    /// called every sample.
+    pub fn set(&mut self, target: f32) {
+        if target == self.target {
+            return;
+        }
+
+        self.target = target;
+
+        if self.ramp_len == 0 {
+            self.current = target;
+            self.steps_left = 0;
+        } else {
+            self.step = (target - self.current) / self.ramp_len as f32;
+            self.steps_left = self.ramp_len;
+        }
+    }
+
+    /// jumps straight to `value` without ramping.
+    pub fn reset(&mut self, value: f32) {
+        self.current = value;
+        self.target = value;
+        self.steps_left = 0;
+    }
+
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    /// the current value, without moving along the ramp.
+    pub fn value(&self) -> f32 {
+        self.current
+    }
+
+    pub fn is_smoothing(&self) -> bool {
+        self.steps_left > 0
+    }
+
+    /// moves one sample along the ramp and returns the new value.
+    pub fn tick(&mut self) -> f32 {
+        if self.steps_left > 0 {
+            self.steps_left -= 1;
+            // land exactly on the target so float error can't build up.
+            self.current = if self.steps_left == 0 {
+                self.target
+            } else {
+                self.current + self.step
+            };
+        }
+
+        self.current
+    }
+}
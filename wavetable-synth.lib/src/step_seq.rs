@@ -0,0 +1,91 @@
+use crate::{
+    arp::{NoteDivision, DEFAULT_BPM},
+    config::SAMPLE_RATE,
+};
+use serde::{Deserialize, Serialize};
+
+/// the most steps a sequence can have.
+pub const MAX_STEPS: usize = 32;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    /// (0.0 - 1.0)
+    pub value: f32,
+    /// how much of the step is spent sliding from the last step's value, (0.0 - 1.0). 0.0 jumps
+    /// straight to `value`.
+    pub glide: f32,
+}
+
+/// a tempo synced step sequencer, used as a modulation source. it starts from the first step on
+/// every note & keeps going through the release.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StepSequencer {
+    pub steps: [Step; MAX_STEPS],
+    /// how many of `steps` play before it loops, (1 - `MAX_STEPS`).
+    pub length: usize,
+    pub rate: NoteDivision,
+    /// the tempo `rate` is synced to, in beats per minute.
+    pub bpm: f32,
+    /// which step is playing.
+    step: usize,
+    /// how far into the step it is, in samples.
+    position: f32,
+    /// the value the current step glides from.
+    from: f32,
+    playing: bool,
+}
+
+impl Default for StepSequencer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StepSequencer {
+    pub fn new() -> Self {
+        Self {
+            steps: [Step::default(); MAX_STEPS],
+            length: 16,
+            rate: NoteDivision::Sixteenth,
+            bpm: DEFAULT_BPM,
+            step: 0,
+            position: 0.0,
+            from: 0.0,
+            playing: false,
+        }
+    }
+
+    pub fn press(&mut self) {
+        self.step = 0;
+        self.position = 0.0;
+        // nothing to glide from on the first step.
+        self.from = self.steps[0].value;
+        self.playing = true;
+    }
+
+    pub fn get_sample(&mut self) -> f32 {
+        if !self.playing {
+            return 0.0;
+        }
+
+        let step_len = (self.rate.beats() * 60.0 / self.bpm.max(1.0) * SAMPLE_RATE as f32).max(1.0);
+        let Step { value, glide } = self.steps[self.step];
+        let glide_len = glide.clamp(0.0, 1.0) * step_len;
+
+        let sample = if self.position < glide_len {
+            self.from + (value - self.from) * self.position / glide_len
+        } else {
+            value
+        };
+
+        self.position += 1.0;
+
+        if self.position >= step_len {
+            self.position -= step_len;
+            self.from = value;
+            self.step = (self.step + 1) % self.length.clamp(1, MAX_STEPS);
+        }
+
+        sample
+    }
+}
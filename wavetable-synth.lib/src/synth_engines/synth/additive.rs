@@ -0,0 +1,100 @@
+use crate::{config::OSC_WAVE_TABLE_SIZE, sin, OscWaveTable};
+use core::f64::consts::PI;
+
+/// the highest harmonic a table can hold without aliasing inside the table.
+pub const MAX_HARMONICS: usize = OSC_WAVE_TABLE_SIZE / 2;
+
+/// one sine wave in an additive table.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Harmonic {
+    /// how loud this harmonic is, relative to the others. (negative flips it)
+    pub amplitude: f32,
+    /// where in its cycle this harmonic starts, in cycles. (0.0..1.0)
+    pub phase: f32,
+}
+
+impl Harmonic {
+    pub fn new(amplitude: f32, phase: f32) -> Self {
+        Self { amplitude, phase }
+    }
+}
+
+/// builds a table from sines, `harmonics[0]` is the fundamental, `harmonics[1]` the 2nd harmonic
+/// and so on. the result is normalized to a peak of 1.0.
+pub fn build_additive_table(harmonics: &[Harmonic]) -> OscWaveTable {
+    sum_harmonics(harmonics.iter().copied())
+}
+
+/// a rising saw with `n_harmonics` harmonics.
+pub fn build_saw_table(n_harmonics: usize) -> OscWaveTable {
+    sum_harmonics((1..=n_harmonics).map(|n| {
+        let sign = if n % 2 == 1 { 1.0 } else { -1.0 };
+
+        Harmonic::new(sign / n as f32, 0.0)
+    }))
+}
+
+/// a square with harmonics up to `n_harmonics`. (only the odd ones are used)
+pub fn build_square_table(n_harmonics: usize) -> OscWaveTable {
+    sum_harmonics((1..=n_harmonics).map(|n| {
+        let amplitude = if n % 2 == 1 { 1.0 / n as f32 } else { 0.0 };
+
+        Harmonic::new(amplitude, 0.0)
+    }))
+}
+
+/// a triangle with harmonics up to `n_harmonics`. (only the odd ones are used)
+pub fn build_triangle_table(n_harmonics: usize) -> OscWaveTable {
+    sum_harmonics((1..=n_harmonics).map(|n| {
+        let amplitude = match n % 4 {
+            1 => 1.0 / (n * n) as f32,
+            3 => -1.0 / (n * n) as f32,
+            _ => 0.0,
+        };
+
+        Harmonic::new(amplitude, 0.0)
+    }))
+}
+
+/// a pulse wave with harmonics up to `n_harmonics`, `width` is how much of the cycle is high.
+/// (0.5 => square)
+pub fn build_pulse_table(width: f32, n_harmonics: usize) -> OscWaveTable {
+    let width = width.clamp(0.01, 0.99) as f64;
+
+    // a pulse is a saw minus the same saw delayed by `width`.
+    sum_harmonics((1..=n_harmonics).map(|n| {
+        let n = n as f64;
+        let amplitude = 2.0 * sin(PI * n * width) / n;
+
+        Harmonic::new(amplitude as f32, (0.25 - n * width * 0.5) as f32)
+    }))
+}
+
+fn sum_harmonics(harmonics: impl Iterator<Item = Harmonic>) -> OscWaveTable {
+    let mut wave_table = [0.0; OSC_WAVE_TABLE_SIZE];
+
+    for (i, harmonic) in harmonics.take(MAX_HARMONICS).enumerate() {
+        if harmonic.amplitude == 0.0 {
+            continue;
+        }
+
+        let n = (i + 1) as f64;
+        let phase = harmonic.phase as f64;
+
+        for (j, sample) in wave_table.iter_mut().enumerate() {
+            let t = j as f64 / OSC_WAVE_TABLE_SIZE as f64;
+
+            *sample += harmonic.amplitude * sin(2.0 * PI * (n * t + phase)) as f32;
+        }
+    }
+
+    let peak = wave_table
+        .iter()
+        .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+
+    if peak > 0.0 {
+        wave_table.iter_mut().for_each(|sample| *sample /= peak);
+    }
+
+    wave_table.into_iter().collect()
+}
@@ -6,7 +6,10 @@ use biquad::{Biquad, Coefficients, DirectForm2Transposed, ToHertz, Q_BUTTERWORTH
 
 pub const N_OVERTONES_SAW: usize = 4;
 
+pub mod additive;
+pub mod noise;
 pub mod osc;
+pub mod sub_osc;
 
 pub fn build_sine_table(overtones: &[f64]) -> OscWaveTable {
     let f0 = 440.hz();
@@ -0,0 +1,109 @@
+use super::osc::OscTarget;
+use crate::{rng::Rng, SampleGen};
+use core::fmt::Display;
+use nih_plug::prelude::Enum;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Enum, Serialize, Deserialize)]
+pub enum NoiseColor {
+    /// equal energy at every frequency.
+    White,
+    /// -3 dB/oct, equal energy per octave.
+    Pink,
+    /// -6 dB/oct, a rumble.
+    Brown,
+}
+
+impl Display for NoiseColor {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::White => write!(f, "White"),
+            Self::Pink => write!(f, "Pink"),
+            Self::Brown => write!(f, "Brown"),
+        }
+    }
+}
+
+/// a noise source for breath, air, & percussive transients.
+#[derive(Clone, Copy, Debug)]
+pub struct Noise {
+    rng: Rng,
+    /// the state of the pink noise filters.
+    pink: [f32; 7],
+    /// the state of the brown noise integrator.
+    brown: f32,
+    pub color: NoiseColor,
+    pub level: f32,
+    pub target: OscTarget,
+    /// how much noise is sent to filter 1 & 2, only used when `target` is one of the filters.
+    pub filter_sends: [f32; 2],
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self::new(Rng::default())
+    }
+}
+
+impl Noise {
+    pub fn new(rng: Rng) -> Self {
+        Self {
+            rng,
+            pink: [0.0; 7],
+            brown: 0.0,
+            color: NoiseColor::White,
+            level: 0.25,
+            target: OscTarget::Filter1_2,
+            filter_sends: [1.0; 2],
+        }
+    }
+
+    /// restarts the noise from `seed`, so each voice can get noise of its own.
+    pub fn seed(&mut self, seed: u32) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// the send levels into filter 1 & 2 after taking `self.target` into account.
+    pub fn get_filter_sends(&self) -> [f32; 2] {
+        self.target.filter_sends(self.filter_sends)
+    }
+
+    pub fn get_sample(&mut self) -> f32 {
+        let white = self.rng.next_bipolar();
+
+        let sample = match self.color {
+            NoiseColor::White => white,
+            NoiseColor::Pink => self.pink(white),
+            NoiseColor::Brown => {
+                // leaky so it doesn't wander off into DC.
+                self.brown = (self.brown + 0.02 * white) / 1.02;
+
+                self.brown * 3.5
+            }
+        };
+
+        sample * self.level
+    }
+
+    /// Paul Kellet's refined pink noise filter.
+    fn pink(&mut self, white: f32) -> f32 {
+        let b = &mut self.pink;
+
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.96900 * b[2] + white * 0.153852;
+        b[3] = 0.86650 * b[3] + white * 0.3104856;
+        b[4] = 0.55000 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.0168980;
+        let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+        b[6] = white * 0.115926;
+
+        pink * 0.11
+    }
+}
+
+impl SampleGen for Noise {
+    fn get_sample(&mut self) -> f32 {
+        self.get_sample()
+    }
+}
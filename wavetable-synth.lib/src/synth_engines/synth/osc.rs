@@ -2,14 +2,18 @@ use crate::{
     calculate_modulation,
     common::OscParam,
     config::{OSC_WAVE_TABLE_SIZE, SAMPLE_RATE},
-    midi_to_freq, pow, tanh, ModulationDest, OscWaveTable, SampleGen,
+    rng::Rng,
+    smooth::Smoothed,
+    synth_engines::synth_common::pitch::Pitch,
+    tanh, ModulationDest, OscWaveTable, SampleGen,
 };
 use core::fmt::Display;
 use nih_plug::prelude::Enum;
+use serde::{Deserialize, Serialize};
 
 pub const N_OVERTONES: usize = 8;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Enum)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Enum, Serialize, Deserialize)]
 pub enum OscTarget {
     Filter1,
     Filter2,
@@ -30,11 +34,72 @@ impl Display for OscTarget {
     }
 }
 
+impl OscTarget {
+    /// the send levels into filter 1 & 2 for a source sent to this target.
+    pub fn filter_sends(&self, sends: [f32; 2]) -> [f32; 2] {
+        match self {
+            Self::Filter1 => [sends[0], 0.0],
+            Self::Filter2 => [0.0, sends[1]],
+            Self::Filter1_2 => sends,
+            Self::Effects | Self::DirectOut => [0.0; 2],
+        }
+    }
+}
+
+/// where an oscillator's cycle starts when a note is played.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Enum, Serialize, Deserialize,
+)]
+pub enum PhaseMode {
+    /// starts every note at `Oscillator::phase`.
+    Reset,
+    /// starts every note somewhere random.
+    Random,
+    /// keeps going from wherever the last note left off.
+    #[default]
+    Free,
+}
+
+impl Display for PhaseMode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Reset => write!(f, "Reset"),
+            Self::Random => write!(f, "Random"),
+            Self::Free => write!(f, "Free Running"),
+        }
+    }
+}
+
+/// how an oscillator is modulated by the other oscillators in the voice. sources are indices into
+/// `Voice::oscs` and only oscillators before this one can be used, (i.e. osc 3 can be modulated by
+/// osc 1 & 2, but osc 1 can't be modulated by anything) so there can never be a feedback loop.
+/// a source that breaks that rule is ignored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CrossMod {
+    /// restart this oscillators cycle whenever the source oscillator restarts its cycle.
+    pub sync: Option<usize>,
+    /// linear (through zero) frequency modulation.
+    pub fm: Option<usize>,
+    /// how far the FM source pushes the frequency, 1.0 => +/- 100% of the frequency.
+    pub fm_index: f32,
+    /// phase modulation.
+    pub pm: Option<usize>,
+    /// how far the PM source pushes the phase, 1.0 => +/- one whole cycle.
+    pub pm_index: f32,
+    /// ring modulation.
+    pub ring: Option<usize>,
+    /// how much of the ring modulated signal is used, 1.0 => all ring mod, no dry signal.
+    pub ring_amt: f32,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct WavetableOscillator {
     sample_rate: f32,
     index: f32,
     index_increment: f32,
+    /// if the last sample started a new cycle, how much of its step went past the start of the
+    /// new cycle, as a fraction of the step, (0.0 - 1.0).
+    overshoot: Option<f32>,
 }
 
 impl Default for WavetableOscillator {
@@ -43,6 +108,7 @@ impl Default for WavetableOscillator {
             sample_rate: SAMPLE_RATE as f32,
             index: 0.0,
             index_increment: 0.0,
+            overshoot: None,
         }
     }
 }
@@ -53,6 +119,7 @@ impl WavetableOscillator {
             sample_rate: SAMPLE_RATE as f32,
             index: 0.0,
             index_increment: 0.0,
+            overshoot: None,
         }
     }
 
@@ -62,21 +129,66 @@ impl WavetableOscillator {
     }
 
     pub fn get_sample(&mut self, wave_table: &[f32]) -> f32 {
+        self.get_modulated_sample(wave_table, 0.0, 0.0)
+    }
+
+    /// `fm` scales the frequency by `1.0 + fm`, `pm` offsets the phase by `pm` cycles.
+    pub fn get_modulated_sample(&mut self, wave_table: &[f32], fm: f32, pm: f32) -> f32 {
         let mut sample = 0.0;
 
-        sample += self.lerp(wave_table);
+        let table_len = OSC_WAVE_TABLE_SIZE as f32;
 
-        self.index += self.index_increment;
-        self.index %= OSC_WAVE_TABLE_SIZE as f32;
+        sample += self.lerp(wave_table, wrap(self.index + pm * table_len, table_len));
+
+        let step = self.index_increment * (1.0 + fm);
+        let index = self.index + step;
+        // through zero FM runs backwards, so the cycle can start again from either end.
+        let past = if index >= table_len {
+            Some(index - table_len)
+        } else if index < 0.0 {
+            Some(index)
+        } else {
+            None
+        };
+        self.overshoot = past.map(|past| (past / step).clamp(0.0, 1.0));
+        self.index = wrap(index, table_len);
 
         sample * 0.9
     }
 
-    fn lerp(&self, wave_table: &[f32]) -> f32 {
-        let truncated_index = self.index as usize;
+    /// restarts the cycle for hard sync, `overshoot` is how much of the master's last step went
+    /// past its restart, (see `overshoot`). this oscillator is put that far into its own step, so
+    /// the sync lands between samples instead of on one.
+    pub fn sync(&mut self, overshoot: f32) {
+        let table_len = OSC_WAVE_TABLE_SIZE as f32;
+
+        self.index = wrap(overshoot * self.index_increment, table_len);
+        self.overshoot = Some(overshoot);
+    }
+
+    /// jumps to `phase` in the cycle, (0.0 - 1.0).
+    pub fn set_phase(&mut self, phase: f32) {
+        let table_len = OSC_WAVE_TABLE_SIZE as f32;
+
+        self.index = wrap(phase * table_len, table_len);
+    }
+
+    /// true if the last sample started a new cycle.
+    pub fn wrapped(&self) -> bool {
+        self.overshoot.is_some()
+    }
+
+    /// if the last sample started a new cycle, how much of its step went past the start of the
+    /// new cycle, as a fraction of the step, (0.0 - 1.0).
+    pub fn overshoot(&self) -> Option<f32> {
+        self.overshoot
+    }
+
+    fn lerp(&self, wave_table: &[f32], index: f32) -> f32 {
+        let truncated_index = index as usize;
         let next_index = (truncated_index + 1) % OSC_WAVE_TABLE_SIZE;
 
-        let next_index_weight = self.index - truncated_index as f32;
+        let next_index_weight = index - truncated_index as f32;
         let truncated_index_weight = 1.0 - next_index_weight;
 
         truncated_index_weight * wave_table[truncated_index]
@@ -84,54 +196,155 @@ impl WavetableOscillator {
     }
 }
 
+/// wraps `index` into `0.0..len`, works for negative numbers too (through zero FM runs backwards).
+fn wrap(index: f32, len: f32) -> f32 {
+    let index = index % len;
+
+    if index < 0.0 {
+        // can round up to len when index is tiny.
+        (index + len) % len
+    } else {
+        index
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Oscillator {
     osc: WavetableOscillator,
-    frequency: f32,
-    base_frequency: f32,
+    /// the note & bend, worked out into a frequency every sample.
+    pitch: Pitch,
     pub level: f32,
+    /// follows `level` so changing it doesn't click.
+    smooth_level: Smoothed,
     level_mod: f32,
     // pan: f32,
-    pub detune: f32,
-    detune_mod: f32,
+    /// fine tuning in cents, (-100.0 - 100.0).
+    pub cents: f32,
+    /// scales `cents`, so the Tune mod does nothing to an oscillator that isn't detuned.
+    cents_mod: f32,
+    /// semitones added on top of the played note.
     pub offset: i16,
+    /// where the cycle starts on each note.
+    pub phase_mode: PhaseMode,
+    /// the start phase when `phase_mode` is `Reset`, (0.0 - 1.0).
+    pub phase: f32,
+    /// how far the phase is pushed from the mod matrix, in cycles.
+    phase_mod: f32,
+    /// picks the start phase when `phase_mode` is `Random`.
+    rng: Rng,
     pub target: OscTarget,
+    /// how much of this oscillator is sent to filter 1 & 2, only used when `target` is one of
+    /// the filters.
+    pub filter_sends: [f32; 2],
+    /// sync, FM, PM, and ring mod from the other oscillators.
+    pub cross_mod: CrossMod,
+    fm_index_mod: f32,
+    pm_index_mod: f32,
+    /// the last sample before level was applied, this is what modulates other oscillators.
+    raw_sample: f32,
     pub wave_table: OscWaveTable,
 }
 
 impl Oscillator {
+    /// the send levels into filter 1 & 2 after taking `self.target` into account.
+    pub fn get_filter_sends(&self) -> [f32; 2] {
+        self.target.filter_sends(self.filter_sends)
+    }
+
     pub fn new(wave_table: OscWaveTable) -> Self {
         Self {
             osc: WavetableOscillator::new(),
-            frequency: 0.0,
-            base_frequency: 0.0,
+            pitch: Pitch::new(),
             level: 1.0,
+            smooth_level: Smoothed::new(1.0),
             level_mod: 0.0,
-            detune: 0.0,
-            detune_mod: 0.0,
+            cents: 0.0,
+            cents_mod: 0.0,
             offset: 0,
+            phase_mode: PhaseMode::Free,
+            phase: 0.0,
+            phase_mod: 0.0,
+            rng: Rng::default(),
             target: OscTarget::Filter1_2,
+            filter_sends: [1.0; 2],
+            cross_mod: CrossMod::default(),
+            fm_index_mod: 0.0,
+            pm_index_mod: 0.0,
+            raw_sample: 0.0,
             wave_table,
         }
     }
 
-    pub fn press(&mut self, midi_note: u8) {
-        let note = midi_note as i16 + self.offset;
-
-        self.frequency = midi_to_freq(note);
-        // log::warn!("midi note: {note}|{midi_note} => {}", self.frequency);
-        self.base_frequency = self.frequency;
+    /// starts a note, `pitch` is a (fractional) midi note, (see `tuning::Tuning::pitch`).
+    pub fn press(&mut self, pitch: f32) {
+        // a bend that's still held carries over to the new note.
+        self.pitch.note = pitch;
+        // a new note starts at the new level rather than fading to it.
+        self.smooth_level.reset(self.level);
+
+        match self.phase_mode {
+            PhaseMode::Reset => self.osc.set_phase(self.phase),
+            PhaseMode::Random => self.osc.set_phase(self.rng.next_f32()),
+            PhaseMode::Free => {}
+        }
+    }
 
-        self.osc.set_frequency(self.frequency);
+    /// reseeds the random start phase, the same seed & notes always give the same phases.
+    pub fn seed_random(&mut self, seed: u32) {
+        self.rng = Rng::new(seed);
     }
 
     pub fn release(&mut self) {}
 
+    /// moves to a (fractional) midi note without restarting anything, (used for glide).
+    pub fn set_pitch(&mut self, note: f32) {
+        self.pitch.note = note;
+    }
+
+    /// sets how long (in seconds) a change to `level` takes to fade in.
+    pub fn set_level_ramp(&mut self, ramp: f32) {
+        self.smooth_level.set_ramp(ramp);
+    }
+
     pub fn get_sample(&mut self) -> f32 {
-        // if self.tune != 0.0 {
-        // self.detune(tune)
-        // }
-        self.detune();
+        self.get_cross_modulated_sample(&[])
+    }
+
+    /// `sources` holds the `cross_mod_source` of each oscillator before this one, see `CrossMod`.
+    pub fn get_cross_modulated_sample(&mut self, sources: &[(f32, Option<f32>)]) -> f32 {
+        let cents = calculate_modulation(self.cents, self.cents_mod);
+        let frequency = self.pitch.frequency(self.offset as f32 + cents / 100.0);
+        self.osc.set_frequency(frequency);
+
+        let source = |i: Option<usize>| i.and_then(|i| sources.get(i)).copied();
+
+        let fm = source(self.cross_mod.fm)
+            .map(|(sample, _)| {
+                sample * calculate_modulation(self.cross_mod.fm_index, self.fm_index_mod)
+            })
+            .unwrap_or(0.0);
+        let pm = source(self.cross_mod.pm)
+            .map(|(sample, _)| {
+                sample * calculate_modulation(self.cross_mod.pm_index, self.pm_index_mod)
+            })
+            .unwrap_or(0.0)
+            + self.phase_mod;
+
+        self.raw_sample = self.osc.get_modulated_sample(&self.wave_table, fm, pm);
+
+        // this sample is still from before the master restarted, the next one is after.
+        if let Some((_, Some(overshoot))) = source(self.cross_mod.sync) {
+            self.osc.sync(overshoot);
+        }
+
+        let sample = match source(self.cross_mod.ring) {
+            Some((ring, _)) => {
+                let amt = self.cross_mod.ring_amt;
+
+                self.raw_sample * (1.0 - amt + amt * ring)
+            }
+            None => self.raw_sample,
+        };
 
         // log::info!(
         //     "modulated volume {}, {}",
@@ -139,55 +352,25 @@ impl Oscillator {
         //     self.level_mod,
         // );
 
-        tanh(
-            self.osc.get_sample(&self.wave_table)
-                * calculate_modulation(self.level, self.level_mod),
-        )
-    }
-
-    pub fn detune(&mut self) {
-        // println!("detuning");
-        if self.detune == 0.0 && self.detune_mod == 0.0 {
-            return;
-        }
-
-        let amt = calculate_modulation(self.detune, self.detune_mod);
+        self.smooth_level.set(self.level);
+        let level = self.smooth_level.tick();
 
-        if amt == 0.0 {
-            return;
-        };
+        tanh(sample * calculate_modulation(level, self.level_mod))
+    }
 
-        // let nudge = 2.0_f32.powf(amt / 12.0);
-        let nudge = pow(2.0, amt / 12.0);
-        let new_freq = if amt < 0.0 {
-            self.frequency / nudge
-        } else if amt > 0.0 {
-            self.frequency * nudge
-        } else {
-            self.frequency
-        };
-        // + self.frequency;
-        self.osc.set_frequency(new_freq);
-        // println!("frequency => {}", self.frequency);
-        // println!("new_freq => {}", new_freq);
-        self.frequency = new_freq;
+    /// the last sample before level & ring mod were applied, and if it started a new cycle how
+    /// much of its step went past the start, (see `WavetableOscillator::overshoot`).
+    pub fn cross_mod_source(&self) -> (f32, Option<f32>) {
+        (self.raw_sample, self.osc.overshoot())
     }
 
+    /// bends the pitch, `bend` goes from -1.0 to 1.0. (see `pitch::BEND_RANGE`)
     pub fn bend(&mut self, bend: f32) {
-        let nudge = pow(2.0, (bend * 3.0) / 12.0);
-        let new_freq = if bend != 0.0 {
-            self.base_frequency * nudge
-        } else {
-            self.base_frequency
-        };
-        self.osc.set_frequency(new_freq);
-        self.frequency = new_freq;
+        self.pitch.bend = bend;
     }
 
     pub fn unbend(&mut self) {
-        // println!("unbend => {}", self.base_frequency);
-        self.osc.set_frequency(self.base_frequency);
-        self.frequency = self.base_frequency;
+        self.pitch.bend = 0.0;
     }
 }
 
@@ -199,7 +382,10 @@ impl ModulationDest for Oscillator {
 
         match what {
             Self::ModTarget::Level => self.level_mod = by,
-            Self::ModTarget::Tune => self.detune_mod = by,
+            Self::ModTarget::Tune => self.cents_mod = by,
+            Self::ModTarget::FmIndex => self.fm_index_mod = by,
+            Self::ModTarget::PmIndex => self.pm_index_mod = by,
+            Self::ModTarget::Phase => self.phase_mod = by,
         }
 
         // log::info!("{}", self.level_mod);
@@ -215,7 +401,10 @@ impl ModulationDest for Oscillator {
         // );
 
         self.level_mod = 0.0;
-        self.detune_mod = 0.0;
+        self.cents_mod = 0.0;
+        self.fm_index_mod = 0.0;
+        self.pm_index_mod = 0.0;
+        self.phase_mod = 0.0;
     }
 }
 
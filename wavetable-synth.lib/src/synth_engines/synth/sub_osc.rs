@@ -0,0 +1,164 @@
+use super::osc::OscTarget;
+use crate::{config::SAMPLE_RATE, synth_engines::synth_common::pitch::Pitch, SampleGen};
+use core::fmt::Display;
+use nih_plug::prelude::Enum;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Enum, Serialize, Deserialize)]
+pub enum SubOctave {
+    Down1,
+    Down2,
+}
+
+impl SubOctave {
+    /// how many semitones below the played note the sub sits.
+    pub fn semitones(&self) -> i16 {
+        match self {
+            Self::Down1 => 12,
+            Self::Down2 => 24,
+        }
+    }
+}
+
+impl Display for SubOctave {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Down1 => write!(f, "-1 Oct"),
+            Self::Down2 => write!(f, "-2 Oct"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Enum, Serialize, Deserialize)]
+pub enum SubShape {
+    Sine,
+    Square,
+}
+
+impl Display for SubShape {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Sine => write!(f, "Sine"),
+            Self::Square => write!(f, "Square"),
+        }
+    }
+}
+
+/// a simple sine/square oscillator that sits one or two octaves under the played note, so a full
+/// wavetable oscillator doesn't have to be spent on it.
+#[derive(Clone, Copy, Debug)]
+pub struct SubOscillator {
+    sample_rate: f32,
+    /// where in the cycle we are. (0.0..1.0)
+    phase: f32,
+    phase_inc: f32,
+    /// the note & bend, worked out into a frequency every sample.
+    pitch: Pitch,
+    pub octave: SubOctave,
+    pub shape: SubShape,
+    pub level: f32,
+    pub target: OscTarget,
+    /// how much of the sub is sent to filter 1 & 2, only used when `target` is one of the filters.
+    pub filter_sends: [f32; 2],
+}
+
+impl Default for SubOscillator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SubOscillator {
+    pub fn new() -> Self {
+        Self {
+            sample_rate: SAMPLE_RATE as f32,
+            phase: 0.0,
+            phase_inc: 0.0,
+            pitch: Pitch::new(),
+            octave: SubOctave::Down1,
+            shape: SubShape::Sine,
+            level: 0.5,
+            target: OscTarget::Filter1_2,
+            filter_sends: [1.0; 2],
+        }
+    }
+
+    /// starts a note, `pitch` is a (fractional) midi note, (see `tuning::Tuning::pitch`).
+    pub fn press(&mut self, pitch: f32) {
+        self.pitch.note = pitch;
+    }
+
+    /// moves to a (fractional) midi note without restarting the cycle, (used for glide).
+    pub fn set_pitch(&mut self, note: f32) {
+        self.pitch.note = note;
+    }
+
+    /// bends the pitch, `bend` goes from -1.0 to 1.0. (see `pitch::BEND_RANGE`)
+    pub fn bend(&mut self, bend: f32) {
+        self.pitch.bend = bend;
+    }
+
+    pub fn unbend(&mut self) {
+        self.pitch.bend = 0.0;
+    }
+
+    fn set_frequency(&mut self, frequency: f32) {
+        self.phase_inc = frequency / self.sample_rate;
+    }
+
+    /// the send levels into filter 1 & 2 after taking `self.target` into account.
+    pub fn get_filter_sends(&self) -> [f32; 2] {
+        self.target.filter_sends(self.filter_sends)
+    }
+
+    pub fn get_sample(&mut self) -> f32 {
+        let frequency = self.pitch.frequency(-self.octave.semitones() as f32);
+        self.set_frequency(frequency);
+
+        let sample = match self.shape {
+            SubShape::Sine => parabolic_sine(self.phase),
+            SubShape::Square => {
+                let naive = if self.phase < 0.5 { 1.0 } else { -1.0 };
+
+                // smooths the two edges to keep the aliasing down.
+                naive + poly_blep(self.phase, self.phase_inc)
+                    - poly_blep((self.phase + 0.5) % 1.0, self.phase_inc)
+            }
+        };
+
+        self.phase = (self.phase + self.phase_inc) % 1.0;
+
+        sample * self.level
+    }
+}
+
+impl SampleGen for SubOscillator {
+    fn get_sample(&mut self) -> f32 {
+        self.get_sample()
+    }
+}
+
+/// a cheap sine approximation (< 0.1% error), `phase` is in cycles. (0.0..1.0)
+fn parabolic_sine(phase: f32) -> f32 {
+    let x = 1.0 - 2.0 * phase;
+    let y = 4.0 * x * (1.0 - x.abs());
+
+    0.225 * (y * y.abs() - y) + y
+}
+
+/// polynomial band limited step, the correction for a jump from -1 to 1 at phase 0.
+fn poly_blep(phase: f32, phase_inc: f32) -> f32 {
+    if phase_inc <= 0.0 {
+        0.0
+    } else if phase < phase_inc {
+        let t = phase / phase_inc;
+
+        2.0 * t - t * t - 1.0
+    } else if phase > 1.0 - phase_inc {
+        let t = (phase - 1.0) / phase_inc;
+
+        t * t + 2.0 * t + 1.0
+    } else {
+        0.0
+    }
+}
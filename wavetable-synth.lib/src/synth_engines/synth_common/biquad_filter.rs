@@ -1,22 +1,28 @@
-use biquad::{Biquad, Coefficients, DirectForm2Transposed, ToHertz, Q_BUTTERWORTH_F32};
+use super::filter::FilterResponse;
+use crate::config::SAMPLE_RATE;
+use biquad::{Biquad, Coefficients, DirectForm2Transposed, ToHertz, Type, Q_BUTTERWORTH_F32};
 
-use crate::{calculate_modulation, common::LowPassParam, config::SAMPLE_RATE, ModulationDest};
+/// the highest Q the resonance param can reach.
+const MAX_Q: f32 = 10.0;
+/// the boost (in dB) of the peak response at full resonance.
+const MAX_PEAK_GAIN: f32 = 24.0;
 
+/// two cascaded biquads, the second one is only used for the 24 dB/oct low-pass.
 #[derive(Clone, Copy, Debug)]
-pub struct BQLowPass {
-    filter: DirectForm2Transposed<f32>,
-    pub cutoff: f32,
-    pub resonance: f32,
-    pub mix: f32,
-    pub note: f32,
-    // pub range: (f32, f32),
-    pub key_track: bool,
-    pub cutoff_mod: f32,
-    pub res_mod: f32,
-    pub mix_mod: f32,
+pub struct BiquadFilter {
+    filters: [DirectForm2Transposed<f32>; 2],
+    coeff_cutoff: f32,
+    coeff_resonance: f32,
+    coeff_response: FilterResponse,
 }
 
-impl BQLowPass {
+impl Default for BiquadFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BiquadFilter {
     pub fn new() -> Self {
         // Cutoff and sampling frequencies
         let f0 = 440.hz();
@@ -24,92 +30,79 @@ impl BQLowPass {
 
         // Create coefficients for the biquads
         let coeffs =
-            Coefficients::<f32>::from_params(biquad::Type::LowPass, fs, f0, Q_BUTTERWORTH_F32)
-                .unwrap();
+            Coefficients::<f32>::from_params(Type::LowPass, fs, f0, Q_BUTTERWORTH_F32).unwrap();
 
         let filter = DirectForm2Transposed::<f32>::new(coeffs);
-        // filter.compute_coeffs(5_000.0, 0.75);
 
         Self {
-            filter,
-            cutoff: 0.5,
-            resonance: 0.5,
-            note: 0.0,
-            // range: (0.0, 0.0),
-            key_track: true,
-            mix: 0.25,
-            cutoff_mod: 0.0,
-            res_mod: 0.0,
-            mix_mod: 0.0,
+            filters: [filter, filter],
+            coeff_cutoff: 440.0,
+            coeff_resonance: 0.0,
+            coeff_response: FilterResponse::LowPass12,
         }
     }
 
-    pub fn set_cutoff(&mut self, cutoff: f32) {
-        self.cutoff = cutoff;
-    }
+    fn compute_coeffs(&mut self, cutoff: f32, resonance: f32, response: FilterResponse) {
+        if self.coeff_cutoff == cutoff
+            && self.coeff_resonance == resonance
+            && self.coeff_response == response
+        {
+            return;
+        }
 
-    pub fn set_resonace(&mut self, res: f32) {
-        self.resonance = res;
-    }
+        // from_params errors if the cutoff is above nyquist.
+        let f0 = cutoff.clamp(10.0, SAMPLE_RATE as f32 * 0.45);
+        let q = Q_BUTTERWORTH_F32 + resonance.clamp(0.0, 1.0) * (MAX_Q - Q_BUTTERWORTH_F32);
+
+        let (first, second) = match response {
+            FilterResponse::LowPass24 => (Type::LowPass, Some(Type::LowPass)),
+            FilterResponse::HighPass => (Type::HighPass, None),
+            FilterResponse::BandPass => (Type::BandPass, None),
+            FilterResponse::Notch => (Type::Notch, None),
+            FilterResponse::Peak => (Type::PeakingEQ(resonance * MAX_PEAK_GAIN), None),
+            FilterResponse::LowPass12 | FilterResponse::Comb => (Type::LowPass, None),
+        };
+
+        let coeffs = |filter_type: Type<f32>, q: f32| {
+            Coefficients::<f32>::from_params(filter_type, SAMPLE_RATE.hz(), f0.hz(), q)
+        };
+
+        // the first stage of the 24 dB slope stays flat so the resonance only comes from the
+        // second stage.
+        let first_q = if second.is_some() {
+            Q_BUTTERWORTH_F32
+        } else {
+            q
+        };
+
+        if let Ok(coeffs) = coeffs(first, first_q) {
+            self.filters[0].update_coefficients(coeffs);
+        }
 
-    pub fn get_sample(&mut self, sample: f32) -> f32 {
-        // let nudge = 2.0_f32.powf(19.0 * env * self.cutoff / 12.0);
-        // // let delta = (self.note * 2.0) - (self.note / 2.0);
-        // // let nudge = delta * env * self.cutoff;
-        // let cutoff = if env > 0.1 {
-        //     self.note * nudge
-        // } else if nudge < 0.1 {
-        //     self.note / nudge
-        // } else {
-        //     self.note
-        // };
-
-        let delta = self.note * 12.0;
-        let nudge = delta * calculate_modulation(self.cutoff, self.cutoff_mod);
-        let cutoff = (self.note) + nudge;
-
-        // warn!("res {}", calculate_modulation(self.resonance, self.res_mod));
-        // let mix = calculate_modulation(self.mix, self.mix_mod);
-
-        self.filter.update_coefficients(
-            Coefficients::<f32>::from_params(
-                biquad::Type::LowPass,
-                SAMPLE_RATE.hz(),
-                cutoff.hz(),
-                Q_BUTTERWORTH_F32,
-            )
-            .unwrap(),
-        );
-
-        // self.filter.process(
-        //     sample,
-        //     cutoff,
-        //     calculate_modulation(self.resonance, self.res_mod),
-        // ) * (1.0 - mix)
-        //     + sample * mix
-
-        self.filter.run(sample)
-    }
+        if let Some(Ok(coeffs)) = second.map(|second| coeffs(second, q)) {
+            self.filters[1].update_coefficients(coeffs);
+        }
 
-    pub fn set_note(&mut self, note: f32) {
-        self.note = note;
+        self.coeff_cutoff = cutoff;
+        self.coeff_resonance = resonance;
+        self.coeff_response = response;
     }
-}
 
-impl ModulationDest for BQLowPass {
-    type ModTarget = LowPassParam;
-
-    fn modulate(&mut self, what: Self::ModTarget, by: f32) {
-        match what {
-            Self::ModTarget::Cutoff => self.cutoff_mod = by,
-            Self::ModTarget::Res => self.res_mod = by,
-            Self::ModTarget::Mix => self.mix_mod = by,
+    pub fn process(
+        &mut self,
+        in_sample: f32,
+        cutoff: f32,
+        resonance: f32,
+        response: FilterResponse,
+    ) -> f32 {
+        self.compute_coeffs(cutoff, resonance, response);
+
+        let sample = self.filters[0].run(in_sample);
+
+        if response == FilterResponse::LowPass24 {
+            self.filters[1].run(sample)
+        } else {
+            sample
         }
     }
-
-    fn reset(&mut self) {
-        self.cutoff_mod = 0.0;
-        self.res_mod = 0.0;
-        self.mix_mod = 0.0;
-    }
 }
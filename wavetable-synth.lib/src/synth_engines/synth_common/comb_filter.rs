@@ -0,0 +1,56 @@
+use crate::config::SAMPLE_RATE;
+
+/// the longest delay the comb can use, sets the lowest cutoff it can reach. (~23 Hz at 48 kHz)
+pub const COMB_BUFFER_SIZE: usize = 2048;
+/// the highest feedback the resonance param can reach.
+const MAX_FEEDBACK: f32 = 0.98;
+
+/// feedback comb filter, its peaks sit on the harmonics of the cutoff frequency.
+#[derive(Clone, Copy, Debug)]
+pub struct CombFilter {
+    buff: [f32; COMB_BUFFER_SIZE],
+    insert_i: usize,
+    sample_rate: f32,
+}
+
+impl Default for CombFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CombFilter {
+    pub fn new() -> Self {
+        Self {
+            buff: [0.0; COMB_BUFFER_SIZE],
+            insert_i: 0,
+            sample_rate: SAMPLE_RATE as f32,
+        }
+    }
+
+    pub fn process(&mut self, in_sample: f32, cutoff: f32, resonance: f32) -> f32 {
+        let max_delay = (COMB_BUFFER_SIZE - 2) as f32;
+        let delay = (self.sample_rate / cutoff.max(1.0)).clamp(1.0, max_delay);
+        let feedback = resonance.clamp(0.0, 1.0) * MAX_FEEDBACK;
+
+        let delayed = self.lerp(delay);
+        let sample = in_sample + feedback * delayed;
+
+        self.buff[self.insert_i] = sample;
+        self.insert_i = (self.insert_i + 1) % COMB_BUFFER_SIZE;
+
+        // normalizes the peaks so turning up the resonance doesn't blow up the volume.
+        sample * (1.0 - feedback)
+    }
+
+    /// reads the buffer `delay` samples in the past.
+    fn lerp(&self, delay: f32) -> f32 {
+        let read_i = self.insert_i as f32 + COMB_BUFFER_SIZE as f32 - delay;
+        let truncated_index = read_i as usize;
+        let next_index_weight = read_i - truncated_index as f32;
+        let truncated_index_weight = 1.0 - next_index_weight;
+
+        truncated_index_weight * self.buff[truncated_index % COMB_BUFFER_SIZE]
+            + next_index_weight * self.buff[(truncated_index + 1) % COMB_BUFFER_SIZE]
+    }
+}
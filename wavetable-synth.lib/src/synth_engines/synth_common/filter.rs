@@ -0,0 +1,372 @@
+use super::{
+    biquad_filter::BiquadFilter, comb_filter::CombFilter, moog_filter::HuovilainenMoog,
+    svf_filter::SvFilter,
+};
+use crate::{
+    calculate_modulation, common::LowPassParam, config::SAMPLE_RATE, pow, smooth::Smoothed, tanh,
+    ModulationDest,
+};
+use core::fmt::Display;
+use nih_plug::prelude::Enum;
+use serde::{Deserialize, Serialize};
+
+/// how long (in seconds) the crossfade between two filter types takes.
+const SWITCH_TIME: f32 = 0.005;
+/// the frequency of middle C, with a key tracking amount of zero the cutoff is based on this.
+const KEY_TRACK_CENTER: f32 = 261.626;
+/// how many octaves the envelope can move the cutoff by at full envelope amount.
+const ENV_RANGE: f32 = 6.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Enum, Serialize, Deserialize)]
+pub enum FilterModel {
+    Moog,
+    Biquad,
+    StateVariable,
+}
+
+impl Display for FilterModel {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Moog => write!(f, "Moog Ladder"),
+            Self::Biquad => write!(f, "Biquad"),
+            Self::StateVariable => write!(f, "State Variable"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Enum, Serialize, Deserialize)]
+pub enum FilterResponse {
+    LowPass12,
+    LowPass24,
+    HighPass,
+    BandPass,
+    Notch,
+    Peak,
+    /// a feedback comb, this one sounds the same no matter the model.
+    Comb,
+}
+
+impl Display for FilterResponse {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::LowPass12 => write!(f, "Low Pass 12"),
+            Self::LowPass24 => write!(f, "Low Pass 24"),
+            Self::HighPass => write!(f, "High Pass"),
+            Self::BandPass => write!(f, "Band Pass"),
+            Self::Notch => write!(f, "Notch"),
+            Self::Peak => write!(f, "Peak"),
+            Self::Comb => write!(f, "Comb"),
+        }
+    }
+}
+
+/// how the two filters are wired together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Enum, Serialize, Deserialize)]
+pub enum FilterRouting {
+    /// filter 1 feeds into filter 2, anything sent to filter 2 joins in at its input.
+    Serial,
+    /// both filters get everything sent to either of them, their outputs are averaged.
+    Parallel,
+    /// each filter only gets what is sent to it & their outputs are summed.
+    Split,
+}
+
+impl Display for FilterRouting {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Serial => write!(f, "Serial"),
+            Self::Parallel => write!(f, "Parallel"),
+            Self::Split => write!(f, "Split"),
+        }
+    }
+}
+
+impl FilterRouting {
+    /// runs the two filter inputs through `filters` and returns the combined output.
+    pub fn route(&self, filters: &mut [Filter; 2], inputs: [f32; 2]) -> f32 {
+        match self {
+            Self::Serial => {
+                let first = filters[0].get_sample(inputs[0]);
+
+                filters[1].get_sample(first + inputs[1])
+            }
+            Self::Parallel => {
+                let input = inputs[0] + inputs[1];
+
+                (filters[0].get_sample(input) + filters[1].get_sample(input)) * 0.5
+            }
+            Self::Split => filters[0].get_sample(inputs[0]) + filters[1].get_sample(inputs[1]),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum FilterEngine {
+    Moog(HuovilainenMoog),
+    Biquad(BiquadFilter),
+    StateVariable(SvFilter),
+    /// runs through `Filter::comb`.
+    Comb,
+}
+
+/// one filter model set to one response.
+#[derive(Clone, Copy, Debug)]
+struct FilterCore {
+    model: FilterModel,
+    response: FilterResponse,
+    engine: FilterEngine,
+}
+
+impl FilterCore {
+    fn new(model: FilterModel, response: FilterResponse) -> Self {
+        let engine = match (model, response) {
+            (_, FilterResponse::Comb) => FilterEngine::Comb,
+            (FilterModel::Moog, _) => FilterEngine::Moog(HuovilainenMoog::new()),
+            (FilterModel::Biquad, _) => FilterEngine::Biquad(BiquadFilter::new()),
+            (FilterModel::StateVariable, _) => FilterEngine::StateVariable(SvFilter::new()),
+        };
+
+        Self {
+            model,
+            response,
+            engine,
+        }
+    }
+
+    fn is(&self, model: FilterModel, response: FilterResponse) -> bool {
+        // the comb sounds the same for every model, so there's never two to fade between.
+        self.response == response && (self.model == model || response == FilterResponse::Comb)
+    }
+
+    fn process(
+        &mut self,
+        comb: &mut CombFilter,
+        sample: f32,
+        cutoff: f32,
+        resonance: f32,
+        drive: f32,
+    ) -> f32 {
+        // the ladder saturates on its own, the rest get soft clipped on the way in.
+        let driven = || {
+            if drive > 0.0 {
+                tanh(sample * (1.0 + 4.0 * drive))
+            } else {
+                sample
+            }
+        };
+
+        match &mut self.engine {
+            FilterEngine::Moog(filter) => {
+                filter.process(sample, cutoff, resonance, drive, self.response)
+            }
+            FilterEngine::Biquad(filter) => {
+                filter.process(driven(), cutoff, resonance, self.response)
+            }
+            FilterEngine::StateVariable(filter) => {
+                filter.process(driven(), cutoff, resonance, self.response)
+            }
+            FilterEngine::Comb => comb.process(driven(), cutoff, resonance),
+        }
+    }
+}
+
+/// a filter slot, the model and response can be changed at any time & the change gets crossfaded.
+#[derive(Clone, Copy, Debug)]
+pub struct Filter {
+    /// the old and new filter, `active` is the one being faded in.
+    cores: [FilterCore; 2],
+    active: usize,
+    /// the comb's delay line, it's too big to keep in `FilterEngine` next to the other models.
+    comb: CombFilter,
+    /// how far through the crossfade we are. (1.0 => done)
+    fade: f32,
+    fade_step: f32,
+    pub model: FilterModel,
+    pub response: FilterResponse,
+    pub cutoff: f32,
+    pub resonance: f32,
+    /// follow `cutoff` & `resonance` so changing them doesn't click or zipper.
+    smooth_cutoff: Smoothed,
+    smooth_resonance: Smoothed,
+    pub mix: f32,
+    /// the frequency of the note being played.
+    pub note: f32,
+    /// how much the cutoff follows the note being played. (0.0 => not at all, 1.0 => fully)
+    pub key_track: f32,
+    /// how much the ladder (or the input for the other models) gets saturated.
+    pub drive: f32,
+    /// which envelope moves the cutoff.
+    pub env: usize,
+    /// how much (and in which direction) the envelope moves the cutoff.
+    pub env_amt: f32,
+    env_sample: f32,
+    pub cutoff_mod: f32,
+    pub res_mod: f32,
+    pub mix_mod: f32,
+    pub key_track_mod: f32,
+    pub drive_mod: f32,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Filter {
+    pub fn new() -> Self {
+        let model = FilterModel::Moog;
+        let response = FilterResponse::LowPass24;
+        let core = FilterCore::new(model, response);
+
+        Self {
+            cores: [core, core],
+            active: 0,
+            comb: CombFilter::new(),
+            fade: 1.0,
+            fade_step: 1.0 / (SWITCH_TIME * SAMPLE_RATE as f32),
+            model,
+            response,
+            cutoff: 0.5,
+            resonance: 0.25,
+            smooth_cutoff: Smoothed::new(0.5),
+            smooth_resonance: Smoothed::new(0.25),
+            note: KEY_TRACK_CENTER,
+            key_track: 1.0,
+            drive: 0.0,
+            env: 1,
+            env_amt: 0.0,
+            env_sample: 0.0,
+            mix: 0.0,
+            cutoff_mod: 0.0,
+            res_mod: 0.0,
+            mix_mod: 0.0,
+            key_track_mod: 0.0,
+            drive_mod: 0.0,
+        }
+    }
+
+    pub fn set_model(&mut self, model: FilterModel) {
+        self.model = model;
+    }
+
+    pub fn set_response(&mut self, response: FilterResponse) {
+        self.response = response;
+    }
+
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        self.cutoff = cutoff;
+    }
+
+    pub fn set_resonace(&mut self, res: f32) {
+        self.resonance = res;
+    }
+
+    pub fn set_note(&mut self, note: f32) {
+        self.note = note;
+    }
+
+    /// sets how long (in seconds) a change to `cutoff` takes to fade in.
+    pub fn set_cutoff_ramp(&mut self, ramp: f32) {
+        self.smooth_cutoff.set_ramp(ramp);
+    }
+
+    /// sets how long (in seconds) a change to `resonance` takes to fade in.
+    pub fn set_resonance_ramp(&mut self, ramp: f32) {
+        self.smooth_resonance.set_ramp(ramp);
+    }
+
+    /// jumps the smoothed params straight to their settings, (for when a voice starts a note).
+    pub fn snap(&mut self) {
+        self.smooth_cutoff.reset(self.cutoff);
+        self.smooth_resonance.reset(self.resonance);
+    }
+
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive = drive;
+    }
+
+    pub fn set_key_track(&mut self, key_track: f32) {
+        self.key_track = key_track;
+    }
+
+    /// sets the current value of the envelope that moves the cutoff, (see `self.env`).
+    pub fn set_env(&mut self, env_sample: f32) {
+        self.env_sample = env_sample;
+    }
+
+    /// the cutoff in Hz for the cutoff setting `cutoff`, after key tracking, the envelope, and
+    /// modulation.
+    fn cutoff_hz(&self, cutoff: f32) -> f32 {
+        let key_track = calculate_modulation(self.key_track, self.key_track_mod).clamp(0.0, 1.0);
+        // interpolates between middle C and the note in the log domain.
+        let base = KEY_TRACK_CENTER * pow(self.note.max(1.0) / KEY_TRACK_CENTER, key_track);
+
+        let delta = base * 16.0;
+        let nudge = delta * calculate_modulation(cutoff, self.cutoff_mod);
+        let cutoff = base + nudge;
+
+        cutoff * pow(2.0, self.env_amt * self.env_sample * ENV_RANGE)
+    }
+
+    /// starts fading to the filter type set in `self.model` & `self.response` if its changed.
+    /// a change made in the middle of a fade waits for that fade to finish.
+    fn switch_core(&mut self) {
+        if self.fade < 1.0 || self.cores[self.active].is(self.model, self.response) {
+            return;
+        }
+
+        self.active = 1 - self.active;
+        self.cores[self.active] = FilterCore::new(self.model, self.response);
+        self.fade = 0.0;
+
+        if self.response == FilterResponse::Comb {
+            self.comb = CombFilter::new();
+        }
+    }
+
+    pub fn get_sample(&mut self, sample: f32) -> f32 {
+        self.switch_core();
+
+        self.smooth_cutoff.set(self.cutoff);
+        self.smooth_resonance.set(self.resonance);
+        let cutoff = self.smooth_cutoff.tick();
+        let cutoff = self.cutoff_hz(cutoff);
+        let resonance = calculate_modulation(self.smooth_resonance.tick(), self.res_mod);
+        let mix = calculate_modulation(self.mix, self.mix_mod);
+        let drive = calculate_modulation(self.drive, self.drive_mod).clamp(0.0, 1.0);
+
+        let comb = &mut self.comb;
+        let mut filtered = self.cores[self.active].process(comb, sample, cutoff, resonance, drive);
+
+        if self.fade < 1.0 {
+            let old = self.cores[1 - self.active].process(comb, sample, cutoff, resonance, drive);
+            filtered = filtered * self.fade + old * (1.0 - self.fade);
+            self.fade = (self.fade + self.fade_step).min(1.0);
+        }
+
+        filtered * (1.0 - mix) + sample * mix
+    }
+}
+
+impl ModulationDest for Filter {
+    type ModTarget = LowPassParam;
+
+    fn modulate(&mut self, what: Self::ModTarget, by: f32) {
+        match what {
+            Self::ModTarget::Cutoff => self.cutoff_mod = by,
+            Self::ModTarget::Res => self.res_mod = by,
+            Self::ModTarget::Mix => self.mix_mod = by,
+            Self::ModTarget::KeyTrack => self.key_track_mod = by,
+            Self::ModTarget::Drive => self.drive_mod = by,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.cutoff_mod = 0.0;
+        self.res_mod = 0.0;
+        self.mix_mod = 0.0;
+        self.key_track_mod = 0.0;
+        self.drive_mod = 0.0;
+    }
+}
@@ -0,0 +1,121 @@
+use crate::smooth::Smoothed;
+use core::fmt::Display;
+use nih_plug::prelude::Enum;
+use serde::{Deserialize, Serialize};
+
+/// the default glide time, in seconds.
+pub const GLIDE_TIME: f32 = 0.1;
+
+/// when a voice slides from its last note to the next one instead of jumping.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Enum, Serialize, Deserialize)]
+pub enum GlideMode {
+    Off,
+    /// every note glides from the one before it.
+    Always,
+    /// only notes played while another is still held glide.
+    Legato,
+}
+
+impl Display for GlideMode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Off => write!(f, "Off"),
+            Self::Always => write!(f, "Always"),
+            Self::Legato => write!(f, "Legato"),
+        }
+    }
+}
+
+/// what `Glide::time` means.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Enum, Serialize, Deserialize)]
+pub enum GlideRate {
+    /// every glide takes `time` seconds, however far it goes.
+    ConstantTime,
+    /// glides take `time` seconds per octave.
+    ConstantRate,
+}
+
+impl Display for GlideRate {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::ConstantTime => write!(f, "Constant Time"),
+            Self::ConstantRate => write!(f, "Constant Rate"),
+        }
+    }
+}
+
+/// portamento, tracks the pitch a voice is at as it slides between notes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glide {
+    pub mode: GlideMode,
+    pub rate: GlideRate,
+    /// in seconds, (see `GlideRate`).
+    pub time: f32,
+    /// the current pitch as a (fractional) midi note.
+    pitch: Smoothed,
+    /// false until the first note, so there's nothing to glide from.
+    started: bool,
+}
+
+impl Default for Glide {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Glide {
+    pub fn new() -> Self {
+        Self {
+            mode: GlideMode::Off,
+            rate: GlideRate::ConstantTime,
+            time: GLIDE_TIME,
+            pitch: Smoothed::with_ramp(0.0, 0.0),
+            started: false,
+        }
+    }
+
+    /// moves to `note`, gliding there if `mode` calls for it. `legato` is whether another note
+    /// was still held when this one was played.
+    pub fn set_note(&mut self, note: f32, legato: bool) {
+        let glide = self.started
+            && match self.mode {
+                GlideMode::Off => false,
+                GlideMode::Always => true,
+                GlideMode::Legato => legato,
+            };
+        self.started = true;
+
+        if !glide {
+            self.pitch.reset(note);
+            return;
+        }
+
+        let time = match self.rate {
+            GlideRate::ConstantTime => self.time,
+            GlideRate::ConstantRate => self.time * (note - self.pitch.value()).abs() / 12.0,
+        };
+
+        self.pitch.set_ramp(time);
+        self.pitch.set(note);
+    }
+
+    /// moves straight to `note`, cutting short any glide that's going.
+    pub fn jump(&mut self, note: f32) {
+        self.started = true;
+        self.pitch.reset(note);
+    }
+
+    pub fn is_gliding(&self) -> bool {
+        self.pitch.is_smoothing()
+    }
+
+    /// the current pitch as a (fractional) midi note.
+    pub fn pitch(&self) -> f32 {
+        self.pitch.value()
+    }
+
+    /// moves one sample along the glide and returns the new pitch.
+    pub fn tick(&mut self) -> f32 {
+        self.pitch.tick()
+    }
+}
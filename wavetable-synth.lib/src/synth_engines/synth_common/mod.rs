@@ -1,3 +1,8 @@
 pub mod biquad_filter;
+pub mod comb_filter;
 pub mod env;
+pub mod filter;
+pub mod glide;
 pub mod moog_filter;
+pub mod pitch;
+pub mod svf_filter;
@@ -1,6 +1,5 @@
-use crate::{
-    calculate_modulation, common::LowPassParam, config::SAMPLE_RATE, exp, tanh, ModulationDest,
-};
+use super::filter::FilterResponse;
+use crate::{config::SAMPLE_RATE, exp, tanh};
 use core::f32::consts::PI;
 
 // Moog filter from
@@ -17,10 +16,16 @@ pub struct HuovilainenMoog {
     res_quad: f32,
     coeff_cutoff: f32,
     coeff_resonance: f32,
+    coeff_drive: f32,
+    /// scales the signal going into the tanh stages, the higher it is the more they saturate.
+    thermal: f32,
     sample_rate: f32,
 }
 
+/// the thermal value at zero drive, this small the stages are pretty much linear.
 const THERMAL: f32 = 0.000025f32;
+/// the thermal value at full drive.
+const MAX_THERMAL: f32 = 2.0;
 
 impl HuovilainenMoog {
     pub fn new() -> Self {
@@ -33,19 +38,37 @@ impl HuovilainenMoog {
             res_quad: 0.0,
             coeff_cutoff: 0.0,
             coeff_resonance: 0.0,
+            coeff_drive: 0.0,
+            thermal: THERMAL,
             sample_rate: SAMPLE_RATE as f32,
         };
 
-        filter.compute_coeffs(5_000.0, 0.5);
+        filter.compute_coeffs(5_000.0, 0.5, 0.0);
 
         filter
     }
 
-    fn compute_coeffs(&mut self, cutoff: f32, resonance: f32) {
-        if self.coeff_cutoff == cutoff && self.coeff_resonance == resonance {
+    fn compute_coeffs(&mut self, cutoff: f32, resonance: f32, drive: f32) {
+        if self.coeff_cutoff == cutoff
+            && self.coeff_resonance == resonance
+            && self.coeff_drive == drive
+        {
             return;
         }
 
+        if self.coeff_drive != drive {
+            let drive = drive.clamp(0.0, 1.0);
+            self.thermal = THERMAL + drive * drive * (MAX_THERMAL - THERMAL);
+
+            // the cached tanh values are scaled by thermal, so they need updating too or the
+            // next sample jumps.
+            let thermal = self.thermal;
+
+            for (stage_tanh, stage) in self.stage_tanh.iter_mut().zip(self.stage) {
+                *stage_tanh = tanh(stage * thermal);
+            }
+        }
+
         let total_cutoff = cutoff.clamp(0.0, self.sample_rate / 2.0);
 
         let fc = total_cutoff / self.sample_rate;
@@ -57,7 +80,7 @@ impl HuovilainenMoog {
         self.acr = -3.9364 * fc2 + 1.8409 * fc + 0.9968;
         // let exponent =;
 
-        self.tune = (1.0 - exp(-1.0 * ((2.0 * PI) * f * fcr))) / THERMAL;
+        self.tune = (1.0 - exp(-1.0 * ((2.0 * PI) * f * fcr))) / self.thermal;
 
         // log::warn!(
         //     "{exp} => {} {} {}",
@@ -70,6 +93,7 @@ impl HuovilainenMoog {
         // Cache the coeffs for the
         self.coeff_cutoff = cutoff;
         self.coeff_resonance = resonance;
+        self.coeff_drive = drive;
     }
 
     pub fn process(
@@ -78,26 +102,29 @@ impl HuovilainenMoog {
         // sample_rate: f32,
         cutoff: f32,
         resonance: f32,
+        drive: f32,
+        response: FilterResponse,
     ) -> f32 {
         // log::warn!("in_sample {in_sample}");
-        self.compute_coeffs(cutoff, resonance);
+        self.compute_coeffs(cutoff, resonance, drive);
+        let mut input = in_sample;
 
         // Oversample
         for _ in 0..2 {
-            let input = in_sample - self.res_quad * self.delay[5];
+            input = in_sample - self.res_quad * self.delay[5];
             self.stage[0] =
-                self.delay[0] + self.tune * (tanh(input * THERMAL) - self.stage_tanh[0]);
+                self.delay[0] + self.tune * (tanh(input * self.thermal) - self.stage_tanh[0]);
             self.delay[0] = self.stage[0];
             for k in 1..4 {
                 let input = self.stage[k - 1];
-                self.stage_tanh[k - 1] = tanh(input * THERMAL);
+                self.stage_tanh[k - 1] = tanh(input * self.thermal);
                 self.stage[k] = self.delay[k]
                     + self.tune
                         * (self.stage_tanh[k - 1]
                             - (if k != 3 {
                                 self.stage_tanh[k]
                             } else {
-                                tanh(self.delay[k] * THERMAL)
+                                tanh(self.delay[k] * self.thermal)
                             }));
                 self.delay[k] = self.stage[k];
             }
@@ -105,96 +132,25 @@ impl HuovilainenMoog {
             self.delay[5] = (self.stage[3] + self.delay[4]) * 0.5;
             self.delay[4] = self.stage[3];
         }
-        self.delay[5] as f32
-    }
-}
-
-#[derive(Clone, Copy, Debug)]
-pub struct LowPass {
-    filter: HuovilainenMoog,
-    pub cutoff: f32,
-    pub resonance: f32,
-    pub mix: f32,
-    pub note: f32,
-    pub key_track: bool,
-    pub cutoff_mod: f32,
-    pub res_mod: f32,
-    pub mix_mod: f32,
-}
-
-impl LowPass {
-    pub fn new() -> Self {
-        let filter = HuovilainenMoog::new();
-        // filter.compute_coeffs(5_000.0, 0.75);
-
-        Self {
-            filter,
-            cutoff: 0.5,
-            resonance: 0.25,
-            note: 0.0,
-            // range: (0.0, 0.0),
-            key_track: true,
-            mix: 0.0,
-            cutoff_mod: 0.0,
-            res_mod: 0.0,
-            mix_mod: 0.0,
-        }
-    }
-
-    pub fn set_cutoff(&mut self, cutoff: f32) {
-        self.cutoff = cutoff;
-    }
-
-    pub fn set_resonace(&mut self, res: f32) {
-        self.resonance = res;
-    }
 
-    pub fn get_sample(&mut self, sample: f32) -> f32 {
-        // let nudge = 2.0_f32.powf(19.0 * env * self.cutoff / 12.0);
-        // // let delta = (self.note * 2.0) - (self.note / 2.0);
-        // // let nudge = delta * env * self.cutoff;
-        // let cutoff = if env > 0.1 {
-        //     self.note * nudge
-        // } else if nudge < 0.1 {
-        //     self.note / nudge
-        // } else {
-        //     self.note
-        // };
-        let delta = self.note * 16.0;
-        let nudge = delta * calculate_modulation(self.cutoff, self.cutoff_mod);
-        let cutoff = (self.note) + nudge;
-        // let cutoff = self.note + self.note * calculate_modulation(self.cutoff, self.cutoff_mod);
-
-        // log::warn!("res {}", calculate_modulation(self.resonance, self.res_mod));
-        let mix = calculate_modulation(self.mix, self.mix_mod);
-
-        self.filter.process(
-            sample,
-            cutoff,
-            calculate_modulation(self.resonance, self.res_mod),
-        ) * (1.0 - mix)
-            + sample * mix
+        self.mix_stages(input, response)
     }
 
-    pub fn set_note(&mut self, note: f32) {
-        self.note = note;
-    }
-}
-
-impl ModulationDest for LowPass {
-    type ModTarget = LowPassParam;
-
-    fn modulate(&mut self, what: Self::ModTarget, by: f32) {
-        match what {
-            Self::ModTarget::Cutoff => self.cutoff_mod = by,
-            Self::ModTarget::Res => self.res_mod = by,
-            Self::ModTarget::Mix => self.mix_mod = by,
+    /// mixes the ladder stages to get the different responses. (the mixing coefficients are the
+    /// binomial ones from "The Art of VA Filter Design" by Vadim Zavalishin.)
+    fn mix_stages(&self, input: f32, response: FilterResponse) -> f32 {
+        let [y1, y2, y3, y4] = self.stage;
+
+        match response {
+            FilterResponse::LowPass24 => self.delay[5],
+            FilterResponse::LowPass12 => y2,
+            FilterResponse::HighPass => input - 4.0 * y1 + 6.0 * y2 - 4.0 * y3 + y4,
+            FilterResponse::BandPass => 4.0 * y2 - 8.0 * y3 + 4.0 * y4,
+            FilterResponse::Notch => input - 2.0 * y1 + 2.0 * y2,
+            // input plus a resonance scaled 2-pole band-pass
+            FilterResponse::Peak => input + 2.0 * self.coeff_resonance * (y1 - y2),
+            // the comb is its own filter, see comb_filter.rs
+            FilterResponse::Comb => self.delay[5],
         }
     }
-
-    fn reset(&mut self) {
-        self.cutoff_mod = 0.0;
-        self.res_mod = 0.0;
-        self.mix_mod = 0.0;
-    }
 }
@@ -0,0 +1,50 @@
+// an oscillator's pitch. everything that moves it (the note, offsets, fine tuning, & bend) is
+// added up in semitones & turned into a frequency in one place, so nothing compounds or drifts.
+
+use crate::note_to_freq;
+
+/// how far a full pitch bend moves the pitch, in semitones.
+pub const BEND_RANGE: f32 = 3.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pitch {
+    /// the (fractional) midi note being played, after tuning & glide.
+    pub note: f32,
+    /// the pitch bend, (-1.0 - 1.0).
+    pub bend: f32,
+    /// the last (semitones, frequency) worked out, so it's only recalculated on a change.
+    last: (f32, f32),
+}
+
+impl Default for Pitch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pitch {
+    pub fn new() -> Self {
+        Self {
+            note: 0.0,
+            bend: 0.0,
+            last: (0.0, note_to_freq(0.0)),
+        }
+    }
+
+    /// the pitch as a (fractional) midi note, `offset` is in semitones (the oscillator's note
+    /// offset, fine tuning, etc).
+    pub fn semitones(&self, offset: f32) -> f32 {
+        self.note + offset + self.bend * BEND_RANGE
+    }
+
+    /// the frequency to play, in Hz. (see `semitones`)
+    pub fn frequency(&mut self, offset: f32) -> f32 {
+        let semitones = self.semitones(offset);
+
+        if semitones != self.last.0 {
+            self.last = (semitones, note_to_freq(semitones));
+        }
+
+        self.last.1
+    }
+}
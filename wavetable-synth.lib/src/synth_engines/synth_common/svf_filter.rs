@@ -0,0 +1,100 @@
+use super::filter::FilterResponse;
+use crate::{config::SAMPLE_RATE, tan};
+use core::f32::consts::PI;
+
+// trapezoidal (zero delay feedback) state variable filter from
+// https://cytomic.com/files/dsp/SvfLinearTrapOptimised2.pdf
+
+#[derive(Clone, Copy, Debug, Default)]
+struct SvfStage {
+    ic1eq: f32,
+    ic2eq: f32,
+}
+
+impl SvfStage {
+    /// returns (low, band, high)
+    fn process(&mut self, v0: f32, g: f32, k: f32) -> (f32, f32, f32) {
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+
+        let v3 = v0 - self.ic2eq;
+        let v1 = a1 * self.ic1eq + a2 * v3;
+        let v2 = self.ic2eq + a2 * self.ic1eq + a3 * v3;
+
+        self.ic1eq = 2.0 * v1 - self.ic1eq;
+        self.ic2eq = 2.0 * v2 - self.ic2eq;
+
+        (v2, v1, v0 - k * v1 - v2)
+    }
+}
+
+/// two cascaded state variable filters, the second one is only used for the 24 dB/oct low-pass.
+#[derive(Clone, Copy, Debug)]
+pub struct SvFilter {
+    stages: [SvfStage; 2],
+    g: f32,
+    k: f32,
+    coeff_cutoff: f32,
+    coeff_resonance: f32,
+    sample_rate: f32,
+}
+
+impl Default for SvFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SvFilter {
+    pub fn new() -> Self {
+        let mut filter = Self {
+            stages: [SvfStage::default(); 2],
+            g: 0.0,
+            k: 0.0,
+            coeff_cutoff: 0.0,
+            coeff_resonance: 0.0,
+            sample_rate: SAMPLE_RATE as f32,
+        };
+
+        filter.compute_coeffs(5_000.0, 0.5);
+
+        filter
+    }
+
+    fn compute_coeffs(&mut self, cutoff: f32, resonance: f32) {
+        if self.coeff_cutoff == cutoff && self.coeff_resonance == resonance {
+            return;
+        }
+
+        let cutoff_hz = cutoff.clamp(10.0, self.sample_rate * 0.45);
+
+        self.g = tan(PI * cutoff_hz / self.sample_rate);
+        // k is 1/Q, at full resonance the filter rings but never blows up.
+        self.k = 2.0 - 1.95 * resonance.clamp(0.0, 1.0);
+
+        self.coeff_cutoff = cutoff;
+        self.coeff_resonance = resonance;
+    }
+
+    pub fn process(
+        &mut self,
+        in_sample: f32,
+        cutoff: f32,
+        resonance: f32,
+        response: FilterResponse,
+    ) -> f32 {
+        self.compute_coeffs(cutoff, resonance);
+
+        let (low, band, high) = self.stages[0].process(in_sample, self.g, self.k);
+
+        match response {
+            FilterResponse::LowPass12 | FilterResponse::Comb => low,
+            FilterResponse::LowPass24 => self.stages[1].process(low, self.g, self.k).0,
+            FilterResponse::HighPass => high,
+            FilterResponse::BandPass => band,
+            FilterResponse::Notch => low + high,
+            FilterResponse::Peak => in_sample + (2.0 - self.k) * band,
+        }
+    }
+}
@@ -0,0 +1,554 @@
+// microtuning. a `Tuning` maps every midi note to a pitch, it can be built from a Scala scale
+// (.scl) & keyboard map (.kbm) or changed note by note with MIDI Tuning Standard SysEx. none of it
+// allocates, so embedded builds can be retuned too.
+
+use crate::{freq_to_note, log2, note_to_freq};
+use anyhow::{bail, Result};
+
+/// the frequency of A4 unless told otherwise, (in Hz).
+pub const A4_FREQ: f32 = 440.0;
+/// the midi note for A4.
+pub const A4_NOTE: u8 = 69;
+/// the most notes a scale can have, (including the period).
+pub const MAX_SCALE_DEGREES: usize = 128;
+
+/// the pitches in a scale, in cents above its root.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scale {
+    /// the last degree is the period, (usually an octave) the scale repeats at.
+    degrees: [f32; MAX_SCALE_DEGREES],
+    len: usize,
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Self::equal_temperament()
+    }
+}
+
+impl Scale {
+    /// 12 tone equal temperament.
+    pub fn equal_temperament() -> Self {
+        let mut degrees = [0.0; MAX_SCALE_DEGREES];
+
+        (0..12).for_each(|i| degrees[i] = (i + 1) as f32 * 100.0);
+
+        Self { degrees, len: 12 }
+    }
+
+    /// reads a Scala scale, (the text of a .scl file).
+    pub fn parse(scl: &str) -> Result<Self> {
+        let mut lines = scl
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.starts_with('!'));
+
+        // the first line is a description, (which may be blank).
+        if lines.next().is_none() {
+            bail!("the scale is empty");
+        }
+
+        let Some(len) = lines.next().map(first_word) else {
+            bail!("the scale doesn't say how many notes it has");
+        };
+        let Ok(len) = len.parse::<usize>() else {
+            bail!("\"{len}\" isn't a number of notes");
+        };
+
+        if len == 0 || len > MAX_SCALE_DEGREES {
+            bail!("a scale needs 1 - {MAX_SCALE_DEGREES} notes, this one has {len}");
+        }
+
+        let mut degrees = [0.0; MAX_SCALE_DEGREES];
+        let mut pitches = lines.filter(|line| !line.is_empty()).map(first_word);
+
+        for (i, degree) in degrees[..len].iter_mut().enumerate() {
+            let Some(pitch) = pitches.next() else {
+                bail!("the scale says it has {len} notes but only {i} are listed");
+            };
+
+            *degree = parse_pitch(pitch)?;
+        }
+
+        Ok(Self { degrees, len })
+    }
+
+    /// reads a Scala scale from a .scl file.
+    #[cfg(feature = "desktop")]
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// how many notes are in the scale, (including the period).
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// the interval the scale repeats at, in cents.
+    pub fn period(&self) -> f32 {
+        self.degrees[self.len - 1]
+    }
+
+    /// how many cents `degree` is above the root, degrees past either end carry on into the next
+    /// period.
+    pub fn cents(&self, degree: i32) -> f32 {
+        let len = self.len as i32;
+        let (period, i) = (degree.div_euclid(len), degree.rem_euclid(len));
+        let cents = if i == 0 {
+            0.0
+        } else {
+            self.degrees[i as usize - 1]
+        };
+
+        period as f32 * self.period() + cents
+    }
+}
+
+/// which keys play which scale degrees & what frequency the scale is tuned to, (a Scala .kbm
+/// file).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyboardMap {
+    /// the scale degree each key in the pattern plays, `None` for keys left unmapped.
+    mapping: [Option<u16>; 128],
+    /// how many keys the pattern has before it repeats, 0 maps every key to the next degree.
+    size: usize,
+    /// keys outside of `first_note..=last_note` aren't retuned.
+    pub first_note: u8,
+    pub last_note: u8,
+    /// the key that plays the scale's root.
+    pub middle_note: u8,
+    /// the key tuned to `reference_freq`.
+    pub reference_note: u8,
+    pub reference_freq: f32,
+    /// the degree each repeat of the pattern is shifted up by, 0 uses the scale's period.
+    pub octave_degree: u16,
+}
+
+impl Default for KeyboardMap {
+    fn default() -> Self {
+        Self::linear(A4_FREQ)
+    }
+}
+
+impl KeyboardMap {
+    /// each key plays the next degree of the scale, rooted on middle C with A4 tuned to `a4`.
+    pub fn linear(a4: f32) -> Self {
+        Self {
+            mapping: [None; 128],
+            size: 0,
+            first_note: 0,
+            last_note: 127,
+            middle_note: 60,
+            reference_note: A4_NOTE,
+            reference_freq: a4,
+            octave_degree: 0,
+        }
+    }
+
+    /// reads a Scala keyboard map, (the text of a .kbm file).
+    pub fn parse(kbm: &str) -> Result<Self> {
+        let mut values = kbm
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('!'))
+            .map(first_word);
+        let size = kbm_number(&mut values, "map size")? as usize;
+
+        if size > 128 {
+            bail!("a keyboard map can have at most 128 keys, this one has {size}");
+        }
+
+        let first_note = kbm_note(&mut values, "first note")?;
+        let last_note = kbm_note(&mut values, "last note")?;
+        let middle_note = kbm_note(&mut values, "middle note")?;
+        let reference_note = kbm_note(&mut values, "reference note")?;
+        let reference_freq = kbm_value(&mut values, "reference frequency")?;
+        let Ok(reference_freq) = reference_freq.parse::<f32>() else {
+            bail!("the keyboard map's reference frequency, \"{reference_freq}\", isn't a number");
+        };
+
+        if reference_freq <= 0.0 {
+            bail!("the keyboard map's reference frequency has to be above 0 Hz");
+        }
+
+        let octave_degree = kbm_number(&mut values, "octave degree")?;
+        let mut mapping = [None; 128];
+
+        // keys left off the end of the list are unmapped.
+        for key in mapping[..size].iter_mut() {
+            *key = match values.next() {
+                Some("x") | Some("X") | None => None,
+                Some(degree) => match degree.parse() {
+                    Ok(degree) => Some(degree),
+                    Err(_) => bail!("\"{degree}\" isn't a scale degree"),
+                },
+            };
+        }
+
+        Ok(Self {
+            mapping,
+            size,
+            first_note,
+            last_note,
+            middle_note,
+            reference_note,
+            reference_freq,
+            octave_degree,
+        })
+    }
+
+    /// reads a Scala keyboard map from a .kbm file.
+    #[cfg(feature = "desktop")]
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// how many cents `note` is above the scale's root, `None` if it's unmapped.
+    fn cents(&self, scale: &Scale, note: u8) -> Option<f32> {
+        let offset = note as i32 - self.middle_note as i32;
+
+        if self.size == 0 {
+            return Some(scale.cents(offset));
+        }
+
+        let size = self.size as i32;
+        let (repeat, key) = (offset.div_euclid(size), offset.rem_euclid(size));
+        let degree = self.mapping[key as usize]?;
+        let period = match self.octave_degree {
+            0 => scale.period(),
+            octave => scale.cents(octave as i32),
+        };
+
+        Some(repeat as f32 * period + scale.cents(degree as i32))
+    }
+}
+
+/// the pitch each midi note plays.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tuning {
+    /// the pitch of each note as a (fractional) 12-TET note, (see `note_to_freq`).
+    pitches: [f32; 128],
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self::equal(A4_FREQ)
+    }
+}
+
+impl Tuning {
+    /// 12 tone equal temperament with A4 tuned to `a4`.
+    pub fn equal(a4: f32) -> Self {
+        let offset = freq_to_note(a4) - A4_NOTE as f32;
+        let mut pitches = [0.0; 128];
+
+        pitches
+            .iter_mut()
+            .enumerate()
+            .for_each(|(note, pitch)| *pitch = note as f32 + offset);
+
+        Self { pitches }
+    }
+
+    /// tunes `scale` across the keyboard with `map`. keys the map leaves out stay 12-TET.
+    pub fn new(scale: &Scale, map: &KeyboardMap) -> Result<Self> {
+        let Some(reference) = map.cents(scale, map.reference_note) else {
+            bail!(
+                "the keyboard map's reference note, {}, isn't mapped to a scale degree",
+                map.reference_note
+            );
+        };
+        let mut tuning = Self::equal(A4_FREQ);
+
+        for note in map.first_note..=map.last_note.min(127) {
+            if let Some(cents) = map.cents(scale, note) {
+                tuning.pitches[note as usize] =
+                    freq_to_note(map.reference_freq) + (cents - reference) / 100.0;
+            }
+        }
+
+        Ok(tuning)
+    }
+
+    /// the pitch `note` plays, as a (fractional) 12-TET note.
+    pub fn pitch(&self, note: u8) -> f32 {
+        self.pitches[(note & 0x7F) as usize]
+    }
+
+    /// the frequency `note` plays, in Hz.
+    pub fn freq(&self, note: u8) -> f32 {
+        note_to_freq(self.pitch(note))
+    }
+
+    /// retunes a single note, `pitch` is a (fractional) 12-TET note.
+    pub fn set_pitch(&mut self, note: u8, pitch: f32) {
+        self.pitches[(note & 0x7F) as usize] = pitch;
+    }
+
+    /// retunes notes from a MIDI Tuning Standard SysEx message, (starting at the 0xF0). single
+    /// note tuning changes, (with & without a bank) & bulk dumps are understood. there's only one
+    /// tuning, so tuning program & bank numbers are ignored.
+    pub fn apply_mts(&mut self, message: &[u8]) -> Result<()> {
+        let message = message.strip_suffix(&[0xF7]).unwrap_or(message);
+
+        let [0xF0, 0x7E | 0x7F, _device, 0x08, sub_id, data @ ..] = message else {
+            bail!("not a MIDI Tuning Standard message");
+        };
+
+        match (sub_id, data) {
+            // single note tuning change, ([bank], program, count, [key, pitch]...).
+            (0x02, [_, _, changes @ ..]) | (0x07, [_, _, _, changes @ ..]) => {
+                for change in changes.chunks_exact(4) {
+                    if let Some(pitch) = mts_pitch(&change[1..]) {
+                        self.set_pitch(change[0], pitch);
+                    }
+                }
+            }
+            // bulk dump, (program, 16 character name, [pitch] * 128, checksum).
+            (0x01, [_program, rest @ ..]) if rest.len() >= 16 + 128 * 3 => {
+                for (note, pitch) in rest[16..16 + 128 * 3].chunks_exact(3).enumerate() {
+                    if let Some(pitch) = mts_pitch(pitch) {
+                        self.set_pitch(note as u8, pitch);
+                    }
+                }
+            }
+            (0x01 | 0x02 | 0x07, _) => bail!("MIDI Tuning Standard message is too short"),
+            (sub_id, _) => bail!("MIDI Tuning Standard message type {sub_id:#04x} isn't supported"),
+        }
+
+        Ok(())
+    }
+}
+
+/// a pitch from an MTS message, (semitone, then a 14 bit fraction of a semitone). `None` means
+/// "leave the note alone".
+fn mts_pitch(bytes: &[u8]) -> Option<f32> {
+    match bytes {
+        [0x7F, 0x7F, 0x7F] => None,
+        [semitone, msb, lsb] => {
+            let fraction = ((*msb as u16 & 0x7F) << 7 | *lsb as u16 & 0x7F) as f32 / 16384.0;
+
+            Some((semitone & 0x7F) as f32 + fraction)
+        }
+        _ => None,
+    }
+}
+
+/// the next value in a keyboard map, `what` is used in the error if it's missing.
+fn kbm_value<'a>(values: &mut impl Iterator<Item = &'a str>, what: &str) -> Result<&'a str> {
+    match values.next() {
+        Some(value) => Ok(value),
+        None => bail!("the keyboard map is missing its {what}"),
+    }
+}
+
+fn kbm_number<'a>(values: &mut impl Iterator<Item = &'a str>, what: &str) -> Result<u16> {
+    let value = kbm_value(values, what)?;
+
+    match value.parse() {
+        Ok(number) => Ok(number),
+        Err(_) => bail!("the keyboard map's {what}, \"{value}\", isn't a number"),
+    }
+}
+
+fn kbm_note<'a>(values: &mut impl Iterator<Item = &'a str>, what: &str) -> Result<u8> {
+    match kbm_number(values, what)? {
+        note @ 0..=127 => Ok(note as u8),
+        note => bail!("the keyboard map's {what}, {note}, isn't a midi note"),
+    }
+}
+
+/// the first word in a line, Scala files allow anything after it.
+fn first_word(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or("")
+}
+
+/// a pitch from a Scala scale, in cents. cents have a '.' in them, anything else is a ratio.
+fn parse_pitch(pitch: &str) -> Result<f32> {
+    if pitch.contains('.') {
+        let Ok(cents) = pitch.parse::<f32>() else {
+            bail!("\"{pitch}\" isn't a number of cents");
+        };
+
+        return Ok(cents);
+    }
+
+    let (numerator, denominator) = pitch.split_once('/').unwrap_or((pitch, "1"));
+
+    match (numerator.parse::<u32>(), denominator.parse::<u32>()) {
+        (Ok(numerator), Ok(denominator)) if numerator > 0 && denominator > 0 => {
+            Ok(1200.0 * log2(numerator as f32 / denominator as f32))
+        }
+        _ => bail!("\"{pitch}\" isn't a ratio"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWELVE_EDO: &str = "! 12edo.scl
+!
+12 tone equal temperament
+ 12
+!
+ 100.0
+ 200.0
+ 300.0
+ 400.0
+ 500.0
+ 600.0
+ 700.0
+ 800.0
+ 900.0
+ 1000.0
+ 1100.0
+ 2/1
+";
+
+    /// just intonation major, (5 limit).
+    const JUST_MAJOR: &str = "just major
+7
+9/8
+5/4
+4/3
+3/2
+5/3
+15/8
+2
+";
+
+    /// the white keys play the scale from middle C, tuned to C = 264 Hz, the black keys are left
+    /// alone.
+    const WHITE_KEYS: &str = "! white keys
+12
+0
+127
+60
+60
+264.0
+7
+! the mapping
+0
+x
+1
+x
+2
+3
+x
+4
+x
+5
+x
+6
+";
+
+    fn assert_close(a: f32, b: f32, tolerance: f32) {
+        assert!(
+            (a - b).abs() <= tolerance,
+            "{a} isn't within {tolerance} of {b}"
+        );
+    }
+
+    #[test]
+    fn twelve_edo_scl_is_equal_temperament() {
+        let scale = Scale::parse(TWELVE_EDO).unwrap();
+        let tuning = Tuning::new(&scale, &KeyboardMap::default()).unwrap();
+        let equal = Tuning::equal(A4_FREQ);
+
+        assert_eq!(scale.len(), 12);
+        assert_close(scale.period(), 1200.0, 1e-3);
+
+        for note in 0..128 {
+            assert_close(tuning.pitch(note), equal.pitch(note), 1e-3);
+        }
+
+        assert_close(tuning.freq(A4_NOTE), A4_FREQ, 1e-2);
+    }
+
+    #[test]
+    fn ratio_scale() {
+        let scale = Scale::parse(JUST_MAJOR).unwrap();
+
+        assert_eq!(scale.len(), 7);
+        assert_close(scale.cents(1), 1200.0 * log2(9.0 / 8.0), 1e-2);
+        assert_close(scale.cents(4), 1200.0 * log2(3.0 / 2.0), 1e-2);
+        assert_close(scale.cents(7), 1200.0, 1e-2);
+        // past the end carries on into the next octave.
+        assert_close(scale.cents(9), 1200.0 + 1200.0 * log2(5.0 / 4.0), 1e-2);
+    }
+
+    #[test]
+    fn kbm_with_unmapped_keys() {
+        let scale = Scale::parse(JUST_MAJOR).unwrap();
+        let map = KeyboardMap::parse(WHITE_KEYS).unwrap();
+        let tuning = Tuning::new(&scale, &map).unwrap();
+
+        // C, D, E, G, & the next C up.
+        assert_close(tuning.freq(60), 264.0, 0.05);
+        assert_close(tuning.freq(62), 297.0, 0.05);
+        assert_close(tuning.freq(64), 330.0, 0.05);
+        assert_close(tuning.freq(67), 396.0, 0.05);
+        assert_close(tuning.freq(72), 528.0, 0.1);
+        assert_close(tuning.freq(48), 132.0, 0.05);
+
+        // the black keys stay 12-TET.
+        for note in [61, 63, 66, 68, 70] {
+            assert_close(tuning.pitch(note), Tuning::equal(A4_FREQ).pitch(note), 1e-3);
+        }
+    }
+
+    #[test]
+    fn mts_single_note_change() {
+        let mut tuning = Tuning::equal(A4_FREQ);
+
+        // retunes middle C a quarter tone up, & asks for D to be left alone.
+        tuning
+            .apply_mts(&[
+                0xF0, 0x7F, 0x7F, 0x08, 0x02, 0x00, 0x02, 60, 60, 0x40, 0x00, 62, 0x7F, 0x7F, 0x7F,
+                0xF7,
+            ])
+            .unwrap();
+
+        assert_close(tuning.pitch(60), 60.5, 1e-4);
+        assert_close(tuning.pitch(62), 62.0, 1e-4);
+        assert_close(tuning.pitch(59), 59.0, 1e-4);
+    }
+
+    #[test]
+    fn malformed_input() {
+        assert!(Scale::parse("").is_err());
+        assert!(Scale::parse("no count").is_err());
+        assert!(Scale::parse("bad count\nlots\n").is_err());
+        assert!(Scale::parse("too short\n3\n100.0\n200.0\n").is_err());
+        assert!(Scale::parse("zero denominator\n1\n3/0\n").is_err());
+        assert!(Scale::parse("not a pitch\n1\nfifth\n").is_err());
+
+        assert!(KeyboardMap::parse("12\n0\n127\n60\n").is_err());
+        assert!(KeyboardMap::parse("129\n0\n127\n60\n69\n440.0\n12\n").is_err());
+        assert!(KeyboardMap::parse("0\n0\n127\n60\n69\n-440.0\n12\n").is_err());
+        assert!(KeyboardMap::parse("1\n0\n127\n60\n69\n440.0\n1\nseven\n").is_err());
+
+        // the reference note (C) lands on a key the map leaves out.
+        let map = KeyboardMap::parse("2\n0\n127\n60\n60\n264.0\n7\nx\n0\n").unwrap();
+        let scale = Scale::parse(JUST_MAJOR).unwrap();
+        assert!(Tuning::new(&scale, &map).is_err());
+
+        let mut tuning = Tuning::default();
+        assert!(tuning.apply_mts(&[0xF0, 0x43, 0x10, 0x4C, 0xF7]).is_err());
+        assert!(tuning
+            .apply_mts(&[0xF0, 0x7F, 0x7F, 0x08, 0x02, 0xF7])
+            .is_err());
+        assert!(tuning
+            .apply_mts(&[0xF0, 0x7E, 0x7F, 0x08, 0x01, 0x00, 0xF7])
+            .is_err());
+        assert!(tuning
+            .apply_mts(&[0xF0, 0x7F, 0x7F, 0x08, 0x09, 0x00, 0xF7])
+            .is_err());
+        assert_eq!(tuning, Tuning::default());
+    }
+}
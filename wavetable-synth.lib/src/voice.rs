@@ -1,15 +1,26 @@
 #[cfg(feature = "embeded")]
 use crate::alloc::borrow::ToOwned;
 use crate::{
-    common::{DataTable, ModMatrixDest},
-    config::{N_ENV, N_LFO, N_OSC, SAMPLE_RATE},
+    common::{key_track, DataTable, ModMatrixDest, KEY_CENTER},
+    config::{N_ENV, N_LFO, N_OSC, N_STEP_SEQ, SAMPLE_RATE},
     effects::{chorus::Chorus, /* reverb::Reverb, */ Effect, EffectsModule},
     lfo::LFO,
-    midi_to_freq,
+    note_to_freq,
+    rng::Rng,
+    step_seq::StepSequencer,
     synth_engines::{
-        synth::osc::{OscTarget, Oscillator},
-        synth_common::{env::ADSR, moog_filter::LowPass},
+        synth::{
+            noise::Noise,
+            osc::{OscTarget, Oscillator},
+            sub_osc::SubOscillator,
+        },
+        synth_common::{
+            env::ADSR,
+            filter::{Filter, FilterRouting},
+            glide::Glide,
+        },
     },
+    tuning::Tuning,
     ModMatrix, ModulationDest, OscWaveTable, SampleGen,
 };
 use array_macro::array;
@@ -39,15 +50,30 @@ use core::ops::IndexMut;
 pub struct Voice {
     /// Oscilators holds the osc and if its playing
     pub oscs: [(Oscillator, bool); N_OSC],
+    /// the sub oscillator and if its playing
+    pub sub: (SubOscillator, bool),
+    /// the noise source and if its playing
+    pub noise: (Noise, bool),
     /// env filters
     pub envs: [ADSR; N_ENV],
     /// LFOs
     pub lfos: [LFO; N_LFO],
+    /// step sequencers, (mod sources)
+    pub step_seqs: [StepSequencer; N_STEP_SEQ],
     /// filters
-    pub filters: [LowPass; 2],
-    // pub filters: [crate::synth_common::biquad_filter::BQLowPass; 2],
+    pub filters: [Filter; 2],
+    /// how the filters are wired together
+    pub filter_routing: FilterRouting,
+    /// portamento between notes
+    pub glide: Glide,
     /// what notes this voice is playing
     pub playing: Option<u8>,
+    /// the note the key track mod source is centered on
+    pub key_center: u8,
+    /// the pitch each note plays, (see `set_tuning`)
+    tuning: Tuning,
+    /// draws the random mod source for each note
+    rng: Rng,
     /// effects, holds the effect and if its one or not
     pub effects: [(EffectsModule, bool); 1],
     /// holds the out put of the different modules and also other needed data (velocity, and note).
@@ -99,11 +125,18 @@ impl Voice {
 
         Self {
             oscs,
+            sub: (SubOscillator::new(), false),
+            noise: (Noise::default(), false),
             envs: array![ADSR::new(); N_ENV],
             lfos: array![LFO::new(); N_LFO],
-            filters: [LowPass::new(), LowPass::new()],
-            // filters: [BQLowPass::new(), BQLowPass::new()],
+            step_seqs: [StepSequencer::new(); N_STEP_SEQ],
+            filters: [Filter::new(), Filter::new()],
+            filter_routing: FilterRouting::Split,
+            glide: Glide::new(),
             playing: None,
+            key_center: KEY_CENTER,
+            tuning: Tuning::default(),
+            rng: Rng::default(),
             data_table: DataTable::default(),
             effects,
             level: 1.0,
@@ -114,21 +147,16 @@ impl Voice {
 
     #[cfg(feature = "embeded")]
     pub fn new_2(wave_table: OscWaveTable) -> Self {
-        use crate::synth_common::biquad_filter::BQLowPass;
-
         let effects = [
             (EffectsModule::Chorus(Chorus::new()), false),
             // (EffectsModule::Reverb(Reverb::new()), false),
         ];
         // let lpf = LowPass::new();
-        let mut oscs = [
-            (Oscillator::new(wave_table.clone()), true),
-            (Oscillator::new(wave_table.clone()), true),
-        ];
-        // oscs[0].1 = true;
+        let mut oscs = array![(Oscillator::new(wave_table.clone()), false); N_OSC];
+        oscs[0].1 = true;
         oscs[0].0.level = 0.8;
-        oscs[1].0.offset = -12;
-        oscs[1].0.level = 0.25;
+        let mut sub = SubOscillator::new();
+        sub.level = 0.25;
         let targets = [
             // OscTarget::Effects,
             OscTarget::Filter1,
@@ -159,6 +187,8 @@ impl Voice {
 
         Self {
             oscs,
+            sub: (sub, true),
+            noise: (Noise::default(), false),
             envs: [
                 ADSR::new(),
                 ADSR::new(),
@@ -167,9 +197,14 @@ impl Voice {
                 // ADSR::new(),
             ],
             lfos: [lfo, lfo_2],
-            filters: [LowPass::new(), LowPass::new()],
-            // filters: [BQLowPass::new(), BQLowPass::new()],
+            step_seqs: [StepSequencer::new(); N_STEP_SEQ],
+            filters: [Filter::new(), Filter::new()],
+            filter_routing: FilterRouting::Split,
+            glide: Glide::new(),
             playing: None,
+            key_center: KEY_CENTER,
+            tuning: Tuning::default(),
+            rng: Rng::default(),
             data_table: DataTable::default(),
             effects,
             level: 1.0,
@@ -179,22 +214,110 @@ impl Voice {
     }
 
     pub fn press(&mut self, midi_note: u8, velocity: u8) {
+        self.trigger(midi_note, velocity, false);
+    }
+
+    /// like `press` but for a note played while another is still held, so legato only glide
+    /// kicks in. the envelopes still restart, (see `legato` for when they shouldn't).
+    pub fn press_overlapping(&mut self, midi_note: u8, velocity: u8) {
+        self.trigger(midi_note, velocity, true);
+    }
+
+    /// changes the note being played without restarting the envelopes or lfos, gliding to it if
+    /// `glide` is on.
+    pub fn legato(&mut self, midi_note: u8) {
+        self.glide.set_note(self.tuning.pitch(midi_note), true);
+        self.set_pitch(self.glide.pitch());
+        self.playing = Some(midi_note);
+        self.data_table.note = Some(midi_note);
+        self.data_table.key_track = key_track(midi_note, self.key_center);
+    }
+
+    pub fn tuning(&self) -> &Tuning {
+        &self.tuning
+    }
+
+    /// changes the pitch each note plays, the note that's sounding moves to its new pitch
+    /// straight away.
+    pub fn set_tuning(&mut self, tuning: Tuning) {
+        self.tuning = tuning;
+
+        if let Some(note) = self.playing {
+            self.glide.jump(tuning.pitch(note));
+            self.set_pitch(self.glide.pitch());
+        }
+    }
+
+    /// reseeds the random mod source, random start phases, & noise. the same seed & notes always
+    /// give the same values.
+    pub fn seed_random(&mut self, seed: u32) {
+        // spreads out nearby seeds, (like voice indices) so their first values aren't close. the
+        // + 1 keeps seed 0 off zero, which `Rng::new` swaps for the same value seed 1 gives.
+        self.rng = Rng::new(seed.wrapping_add(1).wrapping_mul(0x9E37_79B9));
+
+        // each oscillator gets its own random start phases.
+        for (osc, _) in self.oscs.iter_mut() {
+            osc.seed_random(self.rng.next_u32());
+        }
+
+        // otherwise every voice plays the same noise, which adds up as one louder noise.
+        self.noise.0.seed(self.rng.next_u32());
+    }
+
+    /// sets the alternate mod source, it's flipped by whatever hands out notes so it alternates
+    /// across all the voices.
+    pub fn set_alternate(&mut self, on: bool) {
+        self.data_table.alternate = if on { 1.0 } else { 0.0 };
+    }
+
+    /// how fast the key for the note being played was let go, (for the release velocity mod
+    /// source).
+    pub fn set_release_velocity(&mut self, velocity: u8) {
+        self.data_table.release_velocity = Some(velocity);
+    }
+
+    fn trigger(&mut self, midi_note: u8, velocity: u8, overlapping: bool) {
         // log::info!("velocity => {velocity}");
+        let pitch = self.tuning.pitch(midi_note);
+
         self.oscs.iter_mut().for_each(|osc| {
             if osc.1 {
-                osc.0.press(midi_note)
+                osc.0.press(pitch)
             }
         });
+        self.sub.0.press(pitch);
         self.envs.iter_mut().for_each(|env| env.press());
         self.filters.iter_mut().for_each(|filter| {
-            if filter.key_track {
-                filter.set_note(midi_to_freq(midi_note as i16));
-            }
+            filter.set_note(note_to_freq(pitch));
+            filter.snap();
         });
         self.lfos.iter_mut().for_each(|lfo| lfo.press());
+        self.step_seqs.iter_mut().for_each(|seq| seq.press());
+        self.glide.set_note(pitch, overlapping);
+
+        if self.glide.is_gliding() {
+            self.set_pitch(self.glide.pitch());
+        }
+
         self.playing = Some(midi_note);
         self.data_table.velocity = Some(velocity);
+        self.data_table.release_velocity = None;
         self.data_table.note = Some(midi_note);
+        self.data_table.key_track = key_track(midi_note, self.key_center);
+        self.data_table.random = self.rng.next_f32();
+    }
+
+    /// retunes the oscilators, the sub, & the filters' key tracking to a (fractional) midi note.
+    fn set_pitch(&mut self, note: f32) {
+        self.oscs.iter_mut().for_each(|(osc, on)| {
+            if *on {
+                osc.set_pitch(note)
+            }
+        });
+        self.sub.0.set_pitch(note);
+        self.filters
+            .iter_mut()
+            .for_each(|filter| filter.set_note(note_to_freq(note)));
     }
 
     pub fn release(&mut self) {
@@ -214,6 +337,27 @@ impl Voice {
         self.playing = None;
     }
 
+    /// bends the pitch of the oscilators and the sub, `bend` goes from -1.0 to 1.0.
+    pub fn bend(&mut self, bend: f32) {
+        self.oscs.iter_mut().for_each(|(osc, on)| {
+            if *on {
+                osc.bend(bend)
+            }
+        });
+        self.sub.0.bend(bend);
+        self.data_table.pitch_bend = bend;
+    }
+
+    /// sets the tempo the step sequencers are synced to, in beats per minute.
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.step_seqs.iter_mut().for_each(|seq| seq.bpm = bpm);
+    }
+
+    /// sets the mod wheel position, (0.0 - 1.0).
+    pub fn set_mod_wheel(&mut self, mod_wheel: f32) {
+        self.data_table.mod_wheel = mod_wheel;
+    }
+
     /// resets the mod matrix along with the effects, lfos, oscilators, etc
     pub fn reset(&mut self) {
         // self.lfos.iter_mut().for_each(|lfo| lfo.index);
@@ -225,6 +369,9 @@ impl Voice {
         self.lfos.iter_mut().for_each(|lfo| lfo.reset());
         self.envs.iter_mut().for_each(|env| env.reset());
         self.filters.iter_mut().for_each(|lp| lp.reset());
+        self.effects
+            .iter_mut()
+            .for_each(|(effect, _on)| effect.reset_mods());
         // TODO: figure out how to reset a modulation of mod amount
 
         // reset self
@@ -268,6 +415,11 @@ impl Voice {
 
                         self.filters[i].modulate(param, amt)
                     }
+                    ModMatrixDest::Effect { effect, param } => {
+                        if let Some((effect, true)) = self.effects.get_mut(effect) {
+                            effect.modulate_param(param, amt);
+                        }
+                    }
                     // ModMatrixDest::LowPass { low_pass, param } => match low_pass {
                     //     LP::LP1 => self.filters[0].modulate(param, amt),
                     //     LP::LP2 => self.filters[1].modulate(param, amt),
@@ -289,12 +441,15 @@ impl Voice {
 
         self.route_mod_matrix(mod_matrix);
 
+        if self.glide.is_gliding() {
+            let pitch = self.glide.tick();
+            self.set_pitch(pitch);
+        }
+
         // calculate envs
-        #[allow(clippy::never_loop)]
         for (i, env) in self.envs.iter_mut().enumerate() {
             let sample = env.get_samnple();
             self.data_table.env[i] = sample;
-            break;
         }
 
         // calculate lfos
@@ -303,47 +458,59 @@ impl Voice {
             self.data_table.lfos[i] = sample;
         }
 
+        for (i, seq) in self.step_seqs.iter_mut().enumerate() {
+            self.data_table.step_seqs[i] = seq.get_sample();
+        }
+
         if !self.envs[0].pressed() && self.data_table.env[0] <= 0.0 {
             self.playing = None;
             self.reset();
             return 0.0;
         }
 
-        let mut output = 0.0;
+        let mut mix = SourceMix::default();
 
-        let mut osc_sample = 0.0;
+        for filter in self.filters.iter_mut() {
+            let env_sample = self.data_table.env.get(filter.env).copied();
+            filter.set_env(env_sample.unwrap_or(0.0));
+        }
+
+        // what each oscilator can use to modulate the ones after it, (see CrossMod)
+        let mut cross_mod_sources = [(0.0, None); N_OSC];
 
-        for (osc, on) in self.oscs.iter_mut() {
+        for (i, (osc, on)) in self.oscs.iter_mut().enumerate() {
             // output += osc.get_sample();
             // output += self.filters[0].get_sample(osc.get_sample());
             // break;
             if on.to_owned() {
                 // continue;
 
-                let sample = osc.get_sample();
+                let sample = osc.get_cross_modulated_sample(&cross_mod_sources[..i]);
+                cross_mod_sources[i] = osc.cross_mod_source();
                 // continue;
 
-                // self.data_table.osc[i] = sample;
+                self.data_table.osc[i] = sample;
 
                 // osc_sample += // self.filters[0].get_sample(sample);
-                osc_sample += match osc.target {
-                    OscTarget::Filter1 => self.filters[0].get_sample(sample),
-                    OscTarget::Filter2 => self.filters[1].get_sample(sample),
-                    OscTarget::Filter1_2 => {
-                        self.filters[0].get_sample(sample) + self.filters[1].get_sample(sample)
-                    }
-                    OscTarget::Effects => sample,
-                    OscTarget::DirectOut => {
-                        // _ => {
-                        output += sample;
-
-                        continue;
-                    }
-                };
+                mix.add(sample, osc.target, osc.get_filter_sends());
             }
         }
 
-        let mut effects_sample = osc_sample;
+        if self.sub.1 {
+            let (sub, _) = &mut self.sub;
+            mix.add(sub.get_sample(), sub.target, sub.get_filter_sends());
+        }
+
+        if self.noise.1 {
+            let (noise, _) = &mut self.noise;
+            mix.add(noise.get_sample(), noise.target, noise.get_filter_sends());
+        }
+
+        // the filters run once per sample no matter how many sources feed them.
+        let filtered = self
+            .filter_routing
+            .route(&mut self.filters, mix.filter_inputs);
+        let mut effects_sample = mix.effects + filtered;
 
         for (effect, on) in self.effects.iter_mut() {
             if on.to_owned() {
@@ -353,7 +520,7 @@ impl Voice {
             }
         }
 
-        output += effects_sample;
+        let output = mix.direct + effects_sample;
 
         // return Some(output);
 
@@ -366,3 +533,24 @@ impl Voice {
         // sample
     }
 }
+
+/// sums up the sound sources in a voice based on where they're sent.
+#[derive(Clone, Copy, Debug, Default)]
+struct SourceMix {
+    filter_inputs: [f32; 2],
+    effects: f32,
+    direct: f32,
+}
+
+impl SourceMix {
+    fn add(&mut self, sample: f32, target: OscTarget, filter_sends: [f32; 2]) {
+        match target {
+            OscTarget::Filter1 | OscTarget::Filter2 | OscTarget::Filter1_2 => {
+                self.filter_inputs[0] += sample * filter_sends[0];
+                self.filter_inputs[1] += sample * filter_sends[1];
+            }
+            OscTarget::Effects => self.effects += sample,
+            OscTarget::DirectOut => self.direct += sample,
+        }
+    }
+}
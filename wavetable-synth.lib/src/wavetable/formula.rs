@@ -0,0 +1,468 @@
+// a tiny expression language for drawing wave tables as text, e.g. `sin(x * 2 * pi) ^ 3` or
+// `x * 2 - 1 + noise() * 0.1 * frame / frames`.
+//
+// variables:
+//   x       where in the cycle the sample is. (0.0..1.0)
+//   frame   which frame is being drawn. (0 for the first frame)
+//   frames  how many frames are being drawn.
+//   pi
+// functions: sin, cos, abs, sqrt, pow(a, b), min(a, b), max(a, b), noise() (-1.0..1.0)
+// operators: + - * / % ^ and parentheses, `^` is power.
+
+#[cfg(feature = "embeded")]
+use crate::alloc::vec::Vec as AllocVec;
+use crate::{config::OSC_WAVE_TABLE_SIZE, pow, rng::Rng, sin, OscWaveTable};
+use anyhow::{bail, Result};
+use core::{f64::consts::PI, str::FromStr};
+#[cfg(not(feature = "embeded"))]
+use std::vec::Vec as AllocVec;
+
+/// how many values a formula can need at once before it's rejected.
+const STACK_SIZE: usize = 32;
+/// how deep brackets, function calls, & minus signs can nest before a formula is rejected.
+const MAX_NESTING: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Const(f32),
+    X,
+    Frame,
+    Frames,
+    Noise,
+    Neg,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    Sin,
+    Cos,
+    Abs,
+    Sqrt,
+    Min,
+    Max,
+}
+
+impl Op {
+    /// how many values this op takes off the stack.
+    fn n_args(&self) -> usize {
+        match self {
+            Self::Const(_) | Self::X | Self::Frame | Self::Frames | Self::Noise => 0,
+            Self::Neg | Self::Sin | Self::Cos | Self::Abs | Self::Sqrt => 1,
+            Self::Add | Self::Sub | Self::Mul | Self::Div | Self::Rem | Self::Pow => 2,
+            Self::Min | Self::Max => 2,
+        }
+    }
+
+    fn function(name: &str) -> Option<Self> {
+        match name {
+            "sin" => Some(Self::Sin),
+            "cos" => Some(Self::Cos),
+            "abs" => Some(Self::Abs),
+            "sqrt" => Some(Self::Sqrt),
+            "pow" => Some(Self::Pow),
+            "min" => Some(Self::Min),
+            "max" => Some(Self::Max),
+            "noise" => Some(Self::Noise),
+            _ => None,
+        }
+    }
+
+    fn variable(name: &str) -> Option<Self> {
+        match name {
+            "x" => Some(Self::X),
+            "frame" => Some(Self::Frame),
+            "frames" => Some(Self::Frames),
+            "pi" => Some(Self::Const(core::f32::consts::PI)),
+            _ => None,
+        }
+    }
+}
+
+/// a compiled formula, its kept as a list of stack ops so evaluating it doesn't allocate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Formula {
+    ops: AllocVec<Op>,
+}
+
+impl FromStr for Formula {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl Formula {
+    pub fn parse(src: &str) -> Result<Self> {
+        let mut parser = Parser {
+            text: src,
+            src: src.as_bytes(),
+            pos: 0,
+            ops: AllocVec::new(),
+            depth: 0,
+            nesting: 0,
+        };
+
+        parser.expr()?;
+        parser.skip_whitespace();
+
+        if parser.pos < parser.src.len() {
+            // the text, not the bytes, so a multi-byte char is shown whole.
+            let c = parser.text[parser.pos..].chars().next().unwrap_or_default();
+            bail!("unexpected '{c}' at {}", parser.pos);
+        }
+
+        Ok(Self { ops: parser.ops })
+    }
+
+    /// works out the sample at `x` (0.0..1.0) of frame `frame` out of `frames`.
+    pub fn eval(&self, x: f32, frame: usize, frames: usize, rng: &mut Rng) -> f32 {
+        // one spare slot so reading `b` for ops that don't use it never goes out of bounds.
+        let mut stack = [0.0; STACK_SIZE + 1];
+        let mut len = 0;
+
+        for op in self.ops.iter() {
+            let n_args = op.n_args();
+            len -= n_args;
+            let a = stack[len];
+            let b = stack[len + 1];
+
+            stack[len] = match op {
+                Op::Const(value) => *value,
+                Op::X => x,
+                Op::Frame => frame as f32,
+                Op::Frames => frames as f32,
+                Op::Noise => rng.next_bipolar(),
+                Op::Neg => -a,
+                Op::Add => a + b,
+                Op::Sub => a - b,
+                Op::Mul => a * b,
+                Op::Div => a / b,
+                Op::Rem => a % b,
+                Op::Pow => pow(a, b),
+                Op::Sin => sin(a as f64) as f32,
+                Op::Cos => sin(a as f64 + PI * 0.5) as f32,
+                Op::Abs => a.abs(),
+                Op::Sqrt => pow(a, 0.5),
+                Op::Min => a.min(b),
+                Op::Max => a.max(b),
+            };
+            len += 1;
+        }
+
+        // the parser makes sure every formula leaves exactly one value on the stack.
+        let sample = stack[0];
+
+        if sample.is_finite() {
+            sample
+        } else {
+            0.0
+        }
+    }
+
+    /// draws a single table, (frame 0 of 1). the same formula always draws the same table.
+    pub fn build_table(&self) -> OscWaveTable {
+        self.build_frame(0, 1, &mut Rng::default())
+    }
+
+    /// draws `n_frames` tables, one per frame.
+    pub fn build_frames(&self, n_frames: usize) -> AllocVec<OscWaveTable> {
+        let mut rng = Rng::default();
+
+        (0..n_frames)
+            .map(|frame| self.build_frame(frame, n_frames, &mut rng))
+            .collect()
+    }
+
+    fn build_frame(&self, frame: usize, frames: usize, rng: &mut Rng) -> OscWaveTable {
+        (0..OSC_WAVE_TABLE_SIZE)
+            .map(|i| {
+                let x = i as f32 / OSC_WAVE_TABLE_SIZE as f32;
+
+                self.eval(x, frame, frames, rng)
+            })
+            .collect()
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    src: &'a [u8],
+    pos: usize,
+    ops: AllocVec<Op>,
+    /// how many values will be on the stack when evaluating up to this point.
+    depth: usize,
+    /// how deep the parser has recursed.
+    nesting: usize,
+}
+
+impl Parser<'_> {
+    fn push(&mut self, op: Op) -> Result<()> {
+        self.depth = self.depth + 1 - op.n_args();
+
+        if self.depth > STACK_SIZE {
+            bail!("formula nests too deep at {}", self.pos);
+        }
+
+        self.ops.push(op);
+
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.src.len() && self.src[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    /// skips whitespace then consumes `c` if its next.
+    fn eat(&mut self, c: u8) -> bool {
+        self.skip_whitespace();
+
+        if self.src.get(self.pos) == Some(&c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<()> {
+        if !self.eat(c) {
+            bail!("expected '{}' at {}", c as char, self.pos);
+        }
+
+        Ok(())
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<()> {
+        self.term()?;
+
+        loop {
+            if self.eat(b'+') {
+                self.term()?;
+                self.push(Op::Add)?;
+            } else if self.eat(b'-') {
+                self.term()?;
+                self.push(Op::Sub)?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    // term := unary (('*' | '/' | '%') unary)*
+    fn term(&mut self) -> Result<()> {
+        self.unary()?;
+
+        loop {
+            if self.eat(b'*') {
+                self.unary()?;
+                self.push(Op::Mul)?;
+            } else if self.eat(b'/') {
+                self.unary()?;
+                self.push(Op::Div)?;
+            } else if self.eat(b'%') {
+                self.unary()?;
+                self.push(Op::Rem)?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    // unary := '-' unary | power
+    fn unary(&mut self) -> Result<()> {
+        self.nesting += 1;
+
+        if self.nesting > MAX_NESTING {
+            bail!("formula nests too deep at {}", self.pos);
+        }
+
+        if self.eat(b'-') {
+            self.unary()?;
+            self.push(Op::Neg)?;
+        } else {
+            self.power()?;
+        }
+
+        self.nesting -= 1;
+
+        Ok(())
+    }
+
+    // power := atom ('^' unary)?, so `-x^2` is `-(x^2)` & `2^-x` works.
+    fn power(&mut self) -> Result<()> {
+        self.atom()?;
+
+        if self.eat(b'^') {
+            self.unary()?;
+            self.push(Op::Pow)?;
+        }
+
+        Ok(())
+    }
+
+    // atom := number | variable | function '(' args ')' | '(' expr ')'
+    fn atom(&mut self) -> Result<()> {
+        self.skip_whitespace();
+        let start = self.pos;
+
+        match self.src.get(self.pos) {
+            Some(b'(') => {
+                self.pos += 1;
+                self.expr()?;
+                self.expect(b')')
+            }
+            Some(c) if c.is_ascii_digit() || *c == b'.' => {
+                while self
+                    .src
+                    .get(self.pos)
+                    .is_some_and(|c| c.is_ascii_digit() || *c == b'.')
+                {
+                    self.pos += 1;
+                }
+
+                let text = &self.text[start..self.pos];
+                let Ok(value) = text.parse() else {
+                    bail!("bad number '{text}' at {start}");
+                };
+
+                self.push(Op::Const(value))
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                while self
+                    .src
+                    .get(self.pos)
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_')
+                {
+                    self.pos += 1;
+                }
+
+                let name = &self.text[start..self.pos];
+
+                if let Some(op) = Op::function(name) {
+                    self.call(name, op)
+                } else if let Some(op) = Op::variable(name) {
+                    self.push(op)
+                } else {
+                    bail!("unknown name '{name}' at {start}");
+                }
+            }
+            Some(_) => {
+                let c = self.text[start..].chars().next().unwrap_or_default();
+                bail!("unexpected '{c}' at {start}")
+            }
+            None => bail!("formula ended early"),
+        }
+    }
+
+    fn call(&mut self, name: &str, op: Op) -> Result<()> {
+        self.expect(b'(')?;
+
+        let mut n_args = 0;
+
+        if !self.eat(b')') {
+            loop {
+                self.expr()?;
+                n_args += 1;
+
+                if self.eat(b')') {
+                    break;
+                } else if !self.eat(b',') {
+                    bail!("expected ',' or ')' at {}", self.pos);
+                }
+            }
+        }
+
+        if n_args != op.n_args() {
+            bail!("{name} takes {} argument(s) but got {n_args}", op.n_args());
+        }
+
+        self.push(op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(src: &str, x: f32) -> f32 {
+        Formula::parse(src)
+            .unwrap()
+            .eval(x, 0, 1, &mut Rng::default())
+    }
+
+    fn error(src: &str) -> String {
+        Formula::parse(src).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("-x^2", 3.0), -9.0);
+        assert_eq!(eval("2^-x", 1.0), 0.5);
+        assert_eq!(eval("2^3^2", 0.0), 512.0);
+        assert_eq!(eval("1 + 2 * 3", 0.0), 7.0);
+        assert_eq!(eval("(1 + 2) * 3", 0.0), 9.0);
+        assert_eq!(eval("7 - 2 - 1", 0.0), 4.0);
+        assert_eq!(eval("8 / 2 / 2", 0.0), 2.0);
+        assert_eq!(eval("--x", 2.0), 2.0);
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(eval("max(x, 1)", 2.0), 2.0);
+        assert_eq!(eval("min(x, 1)", 2.0), 1.0);
+        assert_eq!(eval("pow(x, 2)", 3.0), 9.0);
+        assert_eq!(eval("abs(-x)", 2.0), 2.0);
+        assert!((eval("sin(x * pi / 2)", 1.0) - 1.0).abs() < 1e-5);
+        assert!((eval("cos(x)", 0.0) - 1.0).abs() < 1e-5);
+        assert!(eval("noise()", 0.0).abs() <= 1.0);
+    }
+
+    #[test]
+    fn wrong_argument_count() {
+        assert_eq!(error("sin()"), "sin takes 1 argument(s) but got 0");
+        assert_eq!(error("sin(x, x)"), "sin takes 1 argument(s) but got 2");
+        assert_eq!(error("pow(x)"), "pow takes 2 argument(s) but got 1");
+        assert_eq!(error("noise(x)"), "noise takes 0 argument(s) but got 1");
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let nested = |n: usize| format!("{}x{}", "(".repeat(n), ")".repeat(n));
+
+        assert!(Formula::parse(&nested(MAX_NESTING - 1)).is_ok());
+        assert!(error(&nested(MAX_NESTING)).contains("nests too deep"));
+        assert!(error(&"-".repeat(10_000)).contains("nests too deep"));
+    }
+
+    #[test]
+    fn stack_limit() {
+        // every `x+(` leaves one more value waiting on the stack.
+        let chain = |n: usize| format!("{}x{}", "x+(".repeat(n), ")".repeat(n));
+
+        assert!(Formula::parse(&chain(STACK_SIZE - 1)).is_ok());
+        assert!(error(&chain(STACK_SIZE)).contains("nests too deep"));
+    }
+
+    #[test]
+    fn non_finite_is_zero() {
+        assert_eq!(eval("1 / x", 0.0), 0.0);
+        assert_eq!(eval("sqrt(x)", -1.0), 0.0);
+        assert_eq!(eval("x % 0", 1.0), 0.0);
+        assert_eq!(eval("10 ^ x", 100.0), 0.0);
+    }
+
+    #[test]
+    fn bad_input() {
+        assert_eq!(error("x )"), "unexpected ')' at 2");
+        assert_eq!(error("x é"), "unexpected 'é' at 2");
+        assert_eq!(error("é"), "unexpected 'é' at 0");
+        assert_eq!(error("foo"), "unknown name 'foo' at 0");
+        assert_eq!(error("x +"), "formula ended early");
+    }
+}
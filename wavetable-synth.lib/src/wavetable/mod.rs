@@ -0,0 +1,261 @@
+// helpers for cleaning up & editing wave tables. everything works on plain slices so it works
+// on `OscWaveTable`s (both the std & heapless kind) and on single frames of a bigger table.
+
+use crate::{config::OSC_WAVE_TABLE_SIZE, pow, sin};
+use anyhow::{bail, Result};
+use core::{f64::consts::PI, ops::RangeInclusive};
+
+/// the difference in dB between two octaves of a signal that falls off at 1/f.
+const DB_PER_OCTAVE: f32 = 6.0206;
+
+pub mod formula;
+
+/// scales `table` so its loudest sample is at `target`. a silent table is left alone.
+pub fn normalize_peak(table: &mut [f32], target: f32) {
+    let peak = peak(table);
+
+    if peak > 0.0 {
+        table.iter_mut().for_each(|sample| *sample *= target / peak);
+    }
+}
+
+/// scales `table` so its RMS level is `target`. a silent table is left alone.
+pub fn normalize_rms(table: &mut [f32], target: f32) {
+    if table.is_empty() {
+        return;
+    }
+
+    let mean_square = table.iter().map(|sample| sample * sample).sum::<f32>() / table.len() as f32;
+
+    if mean_square > 0.0 {
+        let rms = pow(mean_square, 0.5);
+        table.iter_mut().for_each(|sample| *sample *= target / rms);
+    }
+}
+
+/// scales all `frames` by the same amount so the loudest sample of the loudest frame is at
+/// `target`, this keeps the level differences between frames.
+pub fn normalize_frames<T: AsMut<[f32]>>(frames: &mut [T], target: f32) {
+    let peak = frames
+        .iter_mut()
+        .fold(0.0_f32, |loudest, frame| loudest.max(peak(frame.as_mut())));
+
+    if peak > 0.0 {
+        frames.iter_mut().for_each(|frame| {
+            frame
+                .as_mut()
+                .iter_mut()
+                .for_each(|sample| *sample *= target / peak)
+        });
+    }
+}
+
+/// removes any DC offset, (makes the table average out to zero).
+pub fn remove_dc(table: &mut [f32]) {
+    if table.is_empty() {
+        return;
+    }
+
+    let mean = table.iter().sum::<f32>() / table.len() as f32;
+    table.iter_mut().for_each(|sample| *sample -= mean);
+}
+
+/// rotates `table` so it starts on its first rising zero crossing, that way a table starts near
+/// zero and hard sync & phase resets don't click. a table that never crosses zero is left alone.
+pub fn rotate_to_zero_crossing(table: &mut [f32]) {
+    let len = table.len();
+
+    let crossing = (0..len).find(|i| {
+        let before = table[(i + len - 1) % len];
+
+        before < 0.0 && table[*i] >= 0.0
+    });
+
+    if let Some(i) = crossing {
+        // start on whichever side of the crossing is closer to zero.
+        let before = (i + len - 1) % len;
+        let start = if table[before].abs() < table[i].abs() {
+            before
+        } else {
+            i
+        };
+
+        table.rotate_left(start);
+    }
+}
+
+/// scales each harmonic of `table` by what `gain` returns for it. `gain` gets the harmonic number
+/// (1 => the fundamental) & DC is left alone. `table.len()` must be a power of two no longer than
+/// `OSC_WAVE_TABLE_SIZE`.
+pub fn map_harmonics(table: &mut [f32], mut gain: impl FnMut(usize) -> f32) -> Result<()> {
+    let len = table.len();
+
+    if !len.is_power_of_two() {
+        bail!("wave table length ({len}) must be a power of two");
+    } else if len > OSC_WAVE_TABLE_SIZE {
+        bail!("wave table length ({len}) must be at most {OSC_WAVE_TABLE_SIZE}");
+    }
+
+    let mut re = [0.0; OSC_WAVE_TABLE_SIZE];
+    let mut im = [0.0; OSC_WAVE_TABLE_SIZE];
+    let (re, im) = (&mut re[..len], &mut im[..len]);
+    re.copy_from_slice(table);
+
+    fft(re, im, false);
+
+    for harmonic in 1..=len / 2 {
+        let gain = gain(harmonic);
+        let mirror = len - harmonic;
+
+        re[harmonic] *= gain;
+        im[harmonic] *= gain;
+
+        // the nyquist bin is its own mirror.
+        if mirror != harmonic {
+            re[mirror] *= gain;
+            im[mirror] *= gain;
+        }
+    }
+
+    fft(re, im, true);
+
+    table.copy_from_slice(re);
+
+    Ok(())
+}
+
+/// removes every harmonic outside of `keep`. (e.g. `1..=8` only keeps the first 8 harmonics)
+pub fn filter_harmonics(table: &mut [f32], keep: RangeInclusive<usize>) -> Result<()> {
+    map_harmonics(table, |harmonic| keep.contains(&harmonic) as u8 as f32)
+}
+
+/// tilts the spectrum by `db_per_octave` around the fundamental. negative values make the table
+/// darker, positive values make it brighter. (-6.0 turns a square into a triangle-ish wave)
+pub fn spectral_tilt(table: &mut [f32], db_per_octave: f32) -> Result<()> {
+    let exponent = db_per_octave / DB_PER_OCTAVE;
+
+    map_harmonics(table, |harmonic| pow(harmonic as f32, exponent))
+}
+
+/// balances the odd harmonics against the even ones. -1.0 => only odd harmonics, 0.0 => no
+/// change, 1.0 => only even harmonics. (the fundamental counts as odd)
+pub fn odd_even_balance(table: &mut [f32], balance: f32) -> Result<()> {
+    let balance = balance.clamp(-1.0, 1.0);
+    let odd = (1.0 - balance).min(1.0);
+    let even = (1.0 + balance).min(1.0);
+
+    map_harmonics(table, |harmonic| if harmonic % 2 == 1 { odd } else { even })
+}
+
+/// reads a single cycle WAV file into a wave table. channels are mixed down, the cycle is
+/// stretched (or squashed) to `OSC_WAVE_TABLE_SIZE`, & the result is peak normalized.
+#[cfg(feature = "desktop")]
+pub fn load_wav(path: impl AsRef<std::path::Path>) -> Result<crate::OscWaveTable> {
+    use hound::{SampleFormat, WavReader};
+
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+    let samples = match spec.sample_format {
+        SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        SampleFormat::Int => {
+            let scale = pow(2.0, spec.bits_per_sample as f32 - 1.0);
+
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+    let cycle: Vec<f32> = samples
+        .chunks(spec.channels.max(1) as usize)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect();
+
+    if cycle.is_empty() {
+        bail!("the WAV file is empty");
+    }
+
+    let mut table: crate::OscWaveTable = (0..OSC_WAVE_TABLE_SIZE)
+        .map(|i| {
+            let pos = i as f32 * cycle.len() as f32 / OSC_WAVE_TABLE_SIZE as f32;
+            let a = cycle[pos as usize];
+            let b = cycle[(pos as usize + 1) % cycle.len()];
+
+            a + (b - a) * pos.fract()
+        })
+        .collect();
+    normalize_peak(&mut table, 1.0);
+
+    Ok(table)
+}
+
+fn peak(table: &[f32]) -> f32 {
+    table
+        .iter()
+        .fold(0.0_f32, |peak, sample| peak.max(sample.abs()))
+}
+
+/// in place radix-2 FFT, `re.len()` must be a power of two. the inverse is scaled by 1/len.
+fn fft(re: &mut [f32], im: &mut [f32], inverse: bool) {
+    let len = re.len();
+
+    if len < 2 {
+        return;
+    }
+
+    // bit reversal permutation.
+    let mut j = 0;
+
+    for i in 1..len {
+        let mut bit = len >> 1;
+
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+
+        j |= bit;
+
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut size = 2;
+
+    while size <= len {
+        let angle = sign * 2.0 * PI / size as f64;
+        let (w_im, w_re) = (sin(angle), sin(angle + PI * 0.5));
+
+        for start in (0..len).step_by(size) {
+            // twiddles are tracked in f64 so the error doesn't build up across the stage.
+            let (mut t_re, mut t_im) = (1.0_f64, 0.0_f64);
+
+            for k in 0..size / 2 {
+                let a = start + k;
+                let b = a + size / 2;
+
+                let b_re = re[b] * t_re as f32 - im[b] * t_im as f32;
+                let b_im = re[b] * t_im as f32 + im[b] * t_re as f32;
+
+                re[b] = re[a] - b_re;
+                im[b] = im[a] - b_im;
+                re[a] += b_re;
+                im[a] += b_im;
+
+                (t_re, t_im) = (t_re * w_re - t_im * w_im, t_re * w_im + t_im * w_re);
+            }
+        }
+
+        size <<= 1;
+    }
+
+    if inverse {
+        let scale = 1.0 / len as f32;
+
+        re.iter_mut().for_each(|x| *x *= scale);
+        im.iter_mut().for_each(|x| *x *= scale);
+    }
+}
@@ -0,0 +1,437 @@
+// golden audio regression tests. each scene (a patch + some notes) is rendered offline and
+// compared to a reference WAV in `tests/golden/`. after an intentional change to the sound,
+// re-record the references with:
+//
+// ```sh
+// BLESS_GOLDEN=1 cargo test --test golden_audio
+// ```
+#![cfg(feature = "desktop")]
+
+use std::{f64::consts::PI, path::PathBuf};
+use wavetable_synth::{
+    common::{LowPass, LowPassParam, ModMatrixDest, ModMatrixItem, ModMatrixSrc},
+    config::SAMPLE_RATE,
+    patch::Patch,
+    synth_engines::{
+        synth::{noise::NoiseColor, osc::OscTarget, sub_osc::SubShape},
+        synth_common::filter::{FilterModel, FilterResponse},
+    },
+    App, SampleGen,
+};
+
+/// the most the render's RMS level of (render - reference) can be, relative to the reference.
+const MAX_RMS_DIFF: f64 = 0.02;
+/// the most the average log spectra of the render & reference can differ by, in dB.
+const MAX_SPECTRAL_DIFF: f64 = 1.0;
+/// how far the detected pitch can drift, in cents.
+const MAX_PITCH_DIFF: f64 = 5.0;
+const FFT_SIZE: usize = 2048;
+/// `Voice`s are big (the chorus buffer alone holds a second of audio) & unoptimized builds copy
+/// them around on the stack a few times, which is more than a test thread gets.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug)]
+enum Event {
+    On(u8, u8),
+    Off(u8),
+}
+
+struct Scene {
+    name: &'static str,
+    patch: Patch,
+    /// (seconds, event)
+    events: Vec<(f32, Event)>,
+    length: f32,
+}
+
+impl Scene {
+    fn new(name: &'static str, length: f32) -> Self {
+        Self {
+            name,
+            patch: Patch::default(),
+            events: Vec::new(),
+            length,
+        }
+    }
+
+    /// plays `note` from `start` to `end`. (in seconds)
+    fn note(mut self, note: u8, start: f32, end: f32) -> Self {
+        self.events.push((start, Event::On(note, 100)));
+        self.events.push((end, Event::Off(note)));
+
+        self
+    }
+
+    fn render(&self) -> Vec<f32> {
+        let mut app = App::default();
+        self.patch.apply(&mut app).unwrap();
+
+        let mut events: Vec<(usize, Event)> = self
+            .events
+            .iter()
+            .map(|(time, event)| ((time * SAMPLE_RATE as f32) as usize, *event))
+            .collect();
+        events.sort_by_key(|(sample, _)| *sample);
+        let mut events = events.into_iter().peekable();
+
+        (0..(self.length * SAMPLE_RATE as f32) as usize)
+            .map(|i| {
+                while let Some((_, event)) = events.next_if(|(sample, _)| *sample <= i) {
+                    match event {
+                        Event::On(note, velocity) => app.play(note, velocity),
+                        Event::Off(note) => app.stop(note),
+                    }
+                }
+
+                app.get_sample()
+            })
+            .collect()
+    }
+
+    fn reference_path(&self) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("golden")
+            .join(format!("{}.wav", self.name))
+    }
+
+    /// renders the scene & checks it against the reference, (or re-records the reference).
+    fn check(&self) {
+        let render = self.render();
+        let path = self.reference_path();
+
+        if std::env::var_os("BLESS_GOLDEN").is_some() {
+            write_reference(&path, &render);
+            return;
+        }
+
+        let Ok(reference) = read_reference(&path) else {
+            panic!(
+                "{}: no reference at {path:?}, record one with BLESS_GOLDEN=1",
+                self.name
+            );
+        };
+
+        assert_eq!(
+            render.len(),
+            reference.len(),
+            "{}: the render is a different length to the reference",
+            self.name
+        );
+
+        let rms_diff = rms_diff(&render, &reference);
+        assert!(
+            rms_diff <= MAX_RMS_DIFF,
+            "{}: RMS difference {rms_diff:.4} is over {MAX_RMS_DIFF}",
+            self.name
+        );
+
+        let spectral_diff = spectral_diff(&render, &reference);
+        assert!(
+            spectral_diff <= MAX_SPECTRAL_DIFF,
+            "{}: spectral difference {spectral_diff:.3} dB is over {MAX_SPECTRAL_DIFF} dB",
+            self.name
+        );
+
+        if let (Some(pitch), Some(ref_pitch)) = (pitch(&render), pitch(&reference)) {
+            let cents = 1200.0 * (pitch / ref_pitch).log2();
+            assert!(
+                cents.abs() <= MAX_PITCH_DIFF,
+                "{}: pitch {pitch:.2} Hz is {cents:.1} cents off the reference ({ref_pitch:.2} Hz)",
+                self.name
+            );
+        }
+    }
+}
+
+/// builds a scene & checks it on a thread with enough stack, (see `STACK_SIZE`).
+fn check(scene: impl FnOnce() -> Scene + Send) {
+    std::thread::scope(|s| {
+        let handle = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(s, || scene().check())
+            .unwrap();
+
+        // the scene's own panic message has already been printed.
+        if handle.join().is_err() {
+            panic!("golden audio check failed");
+        }
+    });
+}
+
+fn write_reference(path: &PathBuf, samples: &[f32]) {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let mut writer = hound::WavWriter::create(path, spec).unwrap();
+
+    for sample in samples {
+        writer
+            .write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+            .unwrap();
+    }
+
+    writer.finalize().unwrap();
+}
+
+fn read_reference(path: &PathBuf) -> hound::Result<Vec<f32>> {
+    hound::WavReader::open(path)?
+        .samples::<i16>()
+        .map(|sample| sample.map(|sample| sample as f32 / i16::MAX as f32))
+        .collect()
+}
+
+fn rms(samples: impl Iterator<Item = f64>) -> f64 {
+    let (sum, n) = samples.fold((0.0, 0), |(sum, n), x| (sum + x * x, n + 1));
+
+    (sum / n.max(1) as f64).sqrt()
+}
+
+/// the RMS level of the difference, relative to the level of the reference.
+fn rms_diff(render: &[f32], reference: &[f32]) -> f64 {
+    let diff = rms(render.iter().zip(reference).map(|(a, b)| (*a - *b) as f64));
+    let level = rms(reference.iter().map(|x| *x as f64));
+
+    // the 16 bit references can't tell apart anything quieter than this.
+    diff / level.max(1e-4)
+}
+
+/// the mean absolute difference between the log magnitude spectra of the two signals, in dB,
+/// averaged over hann windowed frames. bins that are silent in both are skipped.
+fn spectral_diff(render: &[f32], reference: &[f32]) -> f64 {
+    let (mut total, mut n) = (0.0, 0);
+
+    for start in (0..render.len().saturating_sub(FFT_SIZE)).step_by(FFT_SIZE / 2) {
+        let a = magnitudes(&render[start..start + FFT_SIZE]);
+        let b = magnitudes(&reference[start..start + FFT_SIZE]);
+
+        for (a, b) in a.iter().zip(b.iter()) {
+            // about -100 dB, below what matters & what 16 bits can store.
+            if *a < 1e-5 && *b < 1e-5 {
+                continue;
+            }
+
+            total += (20.0 * (a.max(1e-5) / b.max(1e-5)).log10()).abs();
+            n += 1;
+        }
+    }
+
+    total / n.max(1) as f64
+}
+
+fn magnitudes(frame: &[f32]) -> Vec<f64> {
+    let len = frame.len();
+    let mut re: Vec<f64> = frame
+        .iter()
+        .enumerate()
+        .map(|(i, x)| *x as f64 * (0.5 - 0.5 * (2.0 * PI * i as f64 / len as f64).cos()))
+        .collect();
+    let mut im = vec![0.0; len];
+
+    fft(&mut re, &mut im);
+
+    (0..len / 2)
+        .map(|i| (re[i] * re[i] + im[i] * im[i]).sqrt() / len as f64)
+        .collect()
+}
+
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let len = re.len();
+    let mut j = 0;
+
+    for i in 1..len {
+        let mut bit = len >> 1;
+
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+
+        j |= bit;
+
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+
+    while size <= len {
+        let angle = -2.0 * PI / size as f64;
+
+        for start in (0..len).step_by(size) {
+            for k in 0..size / 2 {
+                let (w_re, w_im) = ((angle * k as f64).cos(), (angle * k as f64).sin());
+                let (a, b) = (start + k, start + k + size / 2);
+                let b_re = re[b] * w_re - im[b] * w_im;
+                let b_im = re[b] * w_im + im[b] * w_re;
+
+                re[b] = re[a] - b_re;
+                im[b] = im[a] - b_im;
+                re[a] += b_re;
+                im[a] += b_im;
+            }
+        }
+
+        size <<= 1;
+    }
+}
+
+/// finds the fundamental (in Hz) of the loudest part of `samples` with autocorrelation.
+/// returns `None` if nothing periodic is found.
+fn pitch(samples: &[f32]) -> Option<f64> {
+    const WINDOW: usize = 4096;
+    // 40 Hz to 2 kHz.
+    let (min_lag, max_lag) = (SAMPLE_RATE as usize / 2000, SAMPLE_RATE as usize / 40);
+
+    let start = (0..samples.len().saturating_sub(WINDOW + max_lag + 1))
+        .step_by(WINDOW / 4)
+        .max_by(|a, b| {
+            let level =
+                |start: usize| rms(samples[start..start + WINDOW].iter().map(|x| *x as f64));
+
+            level(*a).total_cmp(&level(*b))
+        })?;
+    let frame = &samples[start..start + WINDOW + max_lag + 1];
+    let correlate = |lag: usize| -> f64 {
+        (0..WINDOW)
+            .map(|i| frame[i] as f64 * frame[i + lag] as f64)
+            .sum()
+    };
+
+    let energy = correlate(0);
+
+    if energy <= 1e-9 {
+        return None;
+    }
+
+    let correlations: Vec<f64> = (0..=max_lag + 1).map(correlate).collect();
+    // the first peak that gets close to the best one, so we don't lock onto an octave below.
+    let best = correlations[min_lag..=max_lag]
+        .iter()
+        .cloned()
+        .fold(f64::MIN, f64::max);
+    let lag = (min_lag..=max_lag).find(|lag| {
+        let c = correlations[*lag];
+
+        c >= best * 0.9 && c >= correlations[lag - 1] && c >= correlations[lag + 1]
+    })?;
+
+    // parabolic interpolation between the lags around the peak.
+    let (a, b, c) = (
+        correlations[lag - 1],
+        correlations[lag],
+        correlations[lag + 1],
+    );
+    let offset = 0.5 * (a - c) / (a - 2.0 * b + c);
+
+    Some(SAMPLE_RATE as f64 / (lag as f64 + offset))
+}
+
+#[test]
+fn init_patch() {
+    check(|| Scene::new("init_patch", 0.5).note(60, 0.0, 0.3));
+}
+
+#[test]
+fn chord() {
+    check(|| {
+        Scene::new("chord", 0.5)
+            .note(48, 0.0, 0.3)
+            .note(52, 0.05, 0.3)
+            .note(55, 0.1, 0.3)
+    });
+}
+
+#[test]
+fn moog_resonant_env_sweep() {
+    check(|| {
+        let mut scene = Scene::new("moog_resonant_env_sweep", 0.5).note(45, 0.0, 0.3);
+        let filter = &mut scene.patch.filters[0];
+        filter.model = FilterModel::Moog;
+        filter.response = FilterResponse::LowPass24;
+        filter.cutoff = 0.1;
+        filter.resonance = 0.8;
+        filter.env_amt = 0.6;
+
+        scene
+    });
+}
+
+#[test]
+fn state_variable_band_pass() {
+    check(|| {
+        let mut scene = Scene::new("state_variable_band_pass", 0.5).note(57, 0.0, 0.3);
+        scene.patch.filters[0].model = FilterModel::StateVariable;
+        scene.patch.filters[0].response = FilterResponse::BandPass;
+        scene.patch.filters[0].resonance = 0.6;
+
+        scene
+    });
+}
+
+#[test]
+fn biquad_high_pass() {
+    check(|| {
+        let mut scene = Scene::new("biquad_high_pass", 0.5).note(40, 0.0, 0.3);
+        scene.patch.filters[0].model = FilterModel::Biquad;
+        scene.patch.filters[0].response = FilterResponse::HighPass;
+        scene.patch.filters[0].cutoff = 0.3;
+
+        scene
+    });
+}
+
+#[test]
+fn lfo_on_cutoff() {
+    check(|| {
+        let mut scene = Scene::new("lfo_on_cutoff", 0.5).note(52, 0.0, 0.4);
+        scene.patch.lfos[0].freq = 8.0;
+        scene.patch.mod_matrix.push(ModMatrixItem {
+            src: ModMatrixSrc::Lfo(0),
+            dest: ModMatrixDest::LowPass {
+                low_pass: LowPass::LP1,
+                param: LowPassParam::Cutoff,
+            },
+            amt: 1.0,
+            bipolar: true,
+        });
+
+        scene
+    });
+}
+
+#[test]
+fn fm_cross_mod() {
+    check(|| {
+        let mut scene = Scene::new("fm_cross_mod", 0.5).note(57, 0.0, 0.3);
+        let modulator = &mut scene.patch.oscs[0];
+        modulator.target = OscTarget::DirectOut;
+        modulator.level = 0.0;
+        let carrier = &mut scene.patch.oscs[1];
+        carrier.enabled = true;
+        carrier.cross_mod.fm = Some(0);
+        carrier.cross_mod.fm_index = 1.5;
+
+        scene
+    });
+}
+
+#[test]
+fn sub_and_noise() {
+    check(|| {
+        let mut scene = Scene::new("sub_and_noise", 0.5).note(64, 0.0, 0.3);
+        scene.patch.sub.enabled = true;
+        scene.patch.sub.shape = SubShape::Square;
+        scene.patch.noise.enabled = true;
+        scene.patch.noise.color = NoiseColor::Pink;
+        scene.patch.noise.level = 0.1;
+
+        scene
+    });
+}
@@ -0,0 +1,91 @@
+// MIDI input filter tests, (which ports get connected to & which channels get through).
+#![cfg(feature = "desktop")]
+
+use wavetable_synth::midi::{MidiInputs, ALL_CHANNELS};
+
+fn inputs(include: &[&str], exclude: &[&str]) -> MidiInputs {
+    MidiInputs {
+        include: include.iter().map(|pattern| pattern.to_string()).collect(),
+        exclude: exclude.iter().map(|pattern| pattern.to_string()).collect(),
+        ..MidiInputs::default()
+    }
+}
+
+/// a mask that only listens to `channels`, (1 - 16).
+fn only(channels: &[u8]) -> MidiInputs {
+    MidiInputs {
+        channels: channels.iter().fold(0, |mask, ch| mask | 1 << (ch - 1)),
+        ..MidiInputs::default()
+    }
+}
+
+#[test]
+fn every_port_by_default() {
+    let inputs = MidiInputs::default();
+
+    assert!(inputs.accepts_port("Midi Through:Midi Through Port-0 14:0"));
+    assert!(inputs.accepts_port("KeyStep 37 MIDI 1"));
+    assert!(inputs.accepts_port(""));
+}
+
+#[test]
+fn include_patterns() {
+    let inputs = inputs(&["keystep", "LAUNCHKEY"], &[]);
+
+    // any pattern, anywhere in the name, ignoring case.
+    assert!(inputs.accepts_port("KeyStep 37 MIDI 1"));
+    assert!(inputs.accepts_port("Launchkey Mini MK3 MIDI 1"));
+    assert!(!inputs.accepts_port("Midi Through:Midi Through Port-0 14:0"));
+}
+
+#[test]
+fn exclude_patterns() {
+    let inputs = inputs(&[], &["through"]);
+
+    assert!(inputs.accepts_port("KeyStep 37 MIDI 1"));
+    assert!(!inputs.accepts_port("Midi Through:Midi Through Port-0 14:0"));
+}
+
+#[test]
+fn exclude_beats_include() {
+    let inputs = inputs(&["midi"], &["through", "DAW"]);
+
+    assert!(inputs.accepts_port("KeyStep 37 MIDI 1"));
+    assert!(!inputs.accepts_port("Midi Through:Midi Through Port-0 14:0"));
+    assert!(!inputs.accepts_port("Launchkey Mini MK3 DAW MIDI"));
+}
+
+#[test]
+fn every_channel_by_default() {
+    let inputs = MidiInputs::default();
+    assert_eq!(inputs.channels, ALL_CHANNELS);
+
+    for channel in 0..16 {
+        assert!(inputs.accepts_message(&[0x90 | channel, 60, 100]));
+    }
+}
+
+#[test]
+fn channel_filter() {
+    let inputs = only(&[1, 10]);
+
+    // note on, note off, CC, & pitch bend on channels 1 & 10 get through.
+    for status in [0x90, 0x80, 0xB0, 0xE0] {
+        assert!(inputs.accepts_message(&[status, 60, 100]));
+        assert!(inputs.accepts_message(&[status | 9, 60, 100]));
+        assert!(!inputs.accepts_message(&[status | 1, 60, 100]));
+        assert!(!inputs.accepts_message(&[status | 15, 60, 100]));
+    }
+}
+
+#[test]
+fn system_messages_ignore_the_channel_filter() {
+    let inputs = only(&[]);
+
+    assert!(!inputs.accepts_message(&[0x90, 60, 100]));
+    // SysEx, clock, & start, plus an empty message.
+    assert!(inputs.accepts_message(&[0xF0, 0x7E, 0x7F, 0xF7]));
+    assert!(inputs.accepts_message(&[0xF8]));
+    assert!(inputs.accepts_message(&[0xFA]));
+    assert!(inputs.accepts_message(&[]));
+}
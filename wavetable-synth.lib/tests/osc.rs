@@ -0,0 +1,283 @@
+// oscillator tests. pitches are measured by counting the cycles an oscillator starts, (see
+// `Oscillator::cross_mod_source`) over a stretch of samples.
+#![cfg(feature = "desktop")]
+
+use wavetable_synth::{
+    config::{OSC_WAVE_TABLE_SIZE, SAMPLE_RATE},
+    note_to_freq,
+    synth_engines::synth::{
+        build_sine_table,
+        osc::{CrossMod, Oscillator, PhaseMode},
+    },
+    synth_engines::synth_common::pitch::BEND_RANGE,
+    voice::Voice,
+    MOD_MATRIX_SIZE,
+};
+
+/// `Voice`s are too big for a test thread's stack in unoptimized builds, (see `golden_audio.rs`).
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+fn osc() -> Oscillator {
+    Oscillator::new(build_sine_table(&[1.0]))
+}
+
+/// a table that ramps from 0.0 to 1.0, so an oscillator's raw sample is its phase, (times 0.9).
+fn ramp_osc() -> Oscillator {
+    let table = (0..OSC_WAVE_TABLE_SIZE)
+        .map(|i| i as f32 / OSC_WAVE_TABLE_SIZE as f32)
+        .collect();
+
+    Oscillator::new(table)
+}
+
+/// a table that's 0.5 all the way through, for a source that doesn't move.
+fn flat_osc() -> Oscillator {
+    Oscillator::new(vec![0.5; OSC_WAVE_TABLE_SIZE])
+}
+
+/// the phase of a ramp oscillator's last sample, (see `ramp_osc`).
+fn phase(osc: &Oscillator) -> f32 {
+    osc.cross_mod_source().0 / 0.9
+}
+
+/// the distance between two phases, going round the cycle whichever way is shorter.
+fn phase_diff(a: f32, b: f32) -> f32 {
+    let diff = (a - b).rem_euclid(1.0);
+
+    diff.min(1.0 - diff)
+}
+
+/// runs `osc` for `seconds` & returns how many cycles it started.
+fn cycles(osc: &mut Oscillator, seconds: f32) -> usize {
+    (0..(seconds * SAMPLE_RATE as f32) as usize)
+        .filter(|_| {
+            osc.get_sample();
+            osc.cross_mod_source().1.is_some()
+        })
+        .count()
+}
+
+#[test]
+fn detune_and_bend_dont_drift() {
+    let mut osc = osc();
+    osc.cents = 7.0;
+    osc.press(60.0);
+    osc.bend(0.5);
+
+    let expected = note_to_freq(60.0 + 0.07 + 0.5 * BEND_RANGE);
+    let first = cycles(&mut osc, 1.0);
+    cycles(&mut osc, 5.0);
+    let last = cycles(&mut osc, 1.0);
+
+    assert!(first.abs_diff(last) <= 1, "{first} cycles, then {last}");
+    assert!(
+        (last as f32 - expected).abs() <= 1.0,
+        "{last} cycles a second, expected {expected}"
+    );
+}
+
+#[test]
+fn hard_sync_restarts_between_samples() {
+    let (master_note, slave_note) = (45.3, 61.17);
+    let mut master = ramp_osc();
+    let mut slave = ramp_osc();
+    master.press(master_note);
+    slave.press(slave_note);
+    slave.cross_mod.sync = Some(0);
+
+    let step = |note: f32| note_to_freq(note) as f64 / SAMPLE_RATE as f64;
+    let ratio = step(slave_note) / step(master_note);
+    let mut master_phase = 0.0_f64;
+    let mut checked = 0;
+
+    for _ in 0..SAMPLE_RATE / 10 {
+        master.get_sample();
+        slave.get_cross_modulated_sample(&[master.cross_mod_source()]);
+
+        // where a perfectly synced slave would be, (it restarts whenever the master does).
+        let expected = (master_phase * ratio).fract() as f32;
+        master_phase = (master_phase + step(master_note)).fract();
+
+        // the ramp table jumps back to 0.0 over its last entry, so skip the ends of the cycle.
+        let edge = 2.0 / OSC_WAVE_TABLE_SIZE as f32;
+
+        if (edge..1.0 - edge).contains(&expected) {
+            assert!(
+                phase_diff(phase(&slave), expected) < 1e-3,
+                "slave phase {} should be {expected}",
+                phase(&slave)
+            );
+            checked += 1;
+        }
+    }
+
+    assert!(checked > 4000);
+}
+
+#[test]
+fn fm_scales_the_frequency() {
+    let mut modulator = flat_osc();
+    let mut carrier = osc();
+    modulator.press(60.0);
+    carrier.press(60.0);
+    carrier.cross_mod = CrossMod {
+        fm: Some(0),
+        fm_index: 1.0,
+        ..CrossMod::default()
+    };
+
+    // the modulator's raw sample is 0.45, so the carrier runs at 1.45 times its frequency.
+    let expected = note_to_freq(60.0) * 1.45;
+    let cycles = (0..SAMPLE_RATE)
+        .filter(|_| {
+            modulator.get_sample();
+            carrier.get_cross_modulated_sample(&[modulator.cross_mod_source()]);
+            carrier.cross_mod_source().1.is_some()
+        })
+        .count();
+
+    assert!(
+        (cycles as f32 - expected).abs() <= 1.0,
+        "{cycles} cycles a second, expected {expected}"
+    );
+}
+
+#[test]
+fn pm_shifts_the_phase() {
+    let mut modulator = flat_osc();
+    let mut carrier = ramp_osc();
+    let mut dry = ramp_osc();
+    modulator.press(60.0);
+    carrier.press(67.0);
+    dry.press(67.0);
+    carrier.cross_mod = CrossMod {
+        pm: Some(0),
+        pm_index: 0.5,
+        ..CrossMod::default()
+    };
+
+    for _ in 0..1000 {
+        modulator.get_sample();
+        carrier.get_cross_modulated_sample(&[modulator.cross_mod_source()]);
+        dry.get_sample();
+
+        // 0.45 from the modulator, times the index.
+        let expected = (phase(&dry) + 0.225).fract();
+
+        if (0.01..0.99).contains(&expected) {
+            assert!(phase_diff(phase(&carrier), expected) < 1e-3);
+        }
+    }
+}
+
+#[test]
+fn ring_mod_multiplies() {
+    let mut modulator = flat_osc();
+    let mut carrier = osc();
+    let mut dry = osc();
+    modulator.press(60.0);
+    carrier.press(64.0);
+    dry.press(64.0);
+    carrier.cross_mod = CrossMod {
+        ring: Some(0),
+        ring_amt: 1.0,
+        ..CrossMod::default()
+    };
+
+    for _ in 0..1000 {
+        modulator.get_sample();
+        let ringed = carrier.get_cross_modulated_sample(&[modulator.cross_mod_source()]);
+        dry.get_sample();
+        // the output is soft clipped, (level 1.0), with an approximation of tanh.
+        let expected = (dry.cross_mod_source().0 * 0.45).tanh();
+
+        assert!(
+            (ringed - expected).abs() < 1e-3,
+            "{ringed} should be {expected}"
+        );
+    }
+}
+
+#[test]
+fn cross_mod_only_uses_earlier_oscillators() {
+    let render = |feedback: bool| {
+        let mut voice = Voice::new(build_sine_table(&[1.0, 2.0, 3.0]));
+        voice.oscs.iter_mut().for_each(|(_, on)| *on = true);
+
+        if feedback {
+            // every source is this oscillator or one after it, so they're all ignored.
+            for (i, (osc, _)) in voice.oscs.iter_mut().enumerate() {
+                osc.cross_mod = CrossMod {
+                    sync: Some(i),
+                    fm: Some(i),
+                    fm_index: 1.0,
+                    pm: Some(i + 1),
+                    pm_index: 1.0,
+                    ring: Some(i),
+                    ring_amt: 1.0,
+                };
+            }
+        }
+
+        voice.press(60, 100);
+        let mod_matrix = [None; MOD_MATRIX_SIZE];
+
+        (0..2000)
+            .map(|_| voice.get_sample(&mod_matrix))
+            .collect::<Vec<f32>>()
+    };
+
+    let (plain, feedback) = std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || (render(false), render(true)))
+            .unwrap()
+            .join()
+            .unwrap()
+    });
+
+    assert!(plain.iter().any(|sample| *sample != 0.0));
+    assert_eq!(plain, feedback);
+}
+
+#[test]
+fn reset_starts_every_note_at_the_phase() {
+    let mut osc = ramp_osc();
+    osc.phase_mode = PhaseMode::Reset;
+    osc.phase = 0.25;
+
+    for note in [60.0, 64.0, 67.3] {
+        osc.press(note);
+        osc.get_sample();
+        assert!(phase_diff(phase(&osc), 0.25) < 1e-4, "{}", phase(&osc));
+
+        // somewhere else in the cycle before the next note.
+        (0..123).for_each(|_| {
+            osc.get_sample();
+        });
+    }
+}
+
+#[test]
+fn random_phases_follow_the_seed() {
+    let start_phases = |seed: u32| {
+        let mut osc = ramp_osc();
+        osc.phase_mode = PhaseMode::Random;
+        osc.seed_random(seed);
+
+        (0..8)
+            .map(|_| {
+                osc.press(60.0);
+                osc.get_sample();
+
+                phase(&osc)
+            })
+            .collect::<Vec<f32>>()
+    };
+
+    let phases = start_phases(7);
+    assert_eq!(phases, start_phases(7));
+    assert_ne!(phases, start_phases(8));
+    // not stuck on one phase.
+    assert!(phases.windows(2).all(|pair| pair[0] != pair[1]));
+}
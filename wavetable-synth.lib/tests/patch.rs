@@ -0,0 +1,147 @@
+// patch loading tests. a patch that points at an env, lfo, osc, etc that doesn't exist should be
+// turned away by `Patch::apply` instead of panicking on the audio thread later on.
+#![cfg(feature = "desktop")]
+
+use wavetable_synth::{
+    common::{EnvParam, LfoParam, ModMatrixDest, ModMatrixItem, ModMatrixSrc, OscParam},
+    config::{N_ENV, N_LFO, N_OSC, N_STEP_SEQ},
+    patch::Patch,
+    App, SampleGen,
+};
+
+/// `App` is too big for a test thread's stack in unoptimized builds, (see `golden_audio.rs`).
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+fn item(src: ModMatrixSrc, dest: ModMatrixDest) -> ModMatrixItem {
+    ModMatrixItem {
+        src,
+        dest,
+        amt: 1.0,
+        bipolar: false,
+    }
+}
+
+/// runs `f` on a thread with enough stack for an `App`.
+fn with_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, f)
+            .unwrap()
+            .join()
+            .unwrap()
+    })
+}
+
+/// applies the patch to a new `App`, then plays a note through whatever got applied.
+fn apply(patch: Patch) -> anyhow::Result<()> {
+    with_stack(|| {
+        let mut app = App::default();
+        let res = patch.apply(&mut app);
+
+        app.play(60, 100);
+        (0..512).for_each(|_| {
+            app.get_sample();
+        });
+
+        res
+    })
+}
+
+fn with_mod(item: ModMatrixItem) -> Patch {
+    let mut patch = Patch::default();
+    patch.mod_matrix.push(item);
+
+    patch
+}
+
+#[test]
+fn in_range_indices() {
+    let patch = with_mod(item(
+        ModMatrixSrc::Env(N_ENV - 1),
+        ModMatrixDest::Osc {
+            osc: N_OSC - 1,
+            param: OscParam::Level,
+        },
+    ));
+
+    assert!(apply(patch).is_ok());
+}
+
+#[test]
+fn bad_mod_sources() {
+    let dest = ModMatrixDest::SynthVolume;
+
+    for src in [
+        ModMatrixSrc::Env(N_ENV),
+        ModMatrixSrc::Lfo(N_LFO),
+        ModMatrixSrc::StepSeq(N_STEP_SEQ),
+    ] {
+        assert!(apply(with_mod(item(src, dest))).is_err(), "{src:?}");
+    }
+}
+
+#[test]
+fn bad_mod_destinations() {
+    let src = ModMatrixSrc::Velocity;
+
+    for dest in [
+        ModMatrixDest::Osc {
+            osc: N_OSC,
+            param: OscParam::Tune,
+        },
+        ModMatrixDest::Env {
+            env: N_ENV,
+            param: EnvParam::Atk,
+        },
+        ModMatrixDest::Lfo {
+            lfo: N_LFO,
+            param: LfoParam::Speed,
+        },
+        ModMatrixDest::ModMatrixEntryModAmt(0),
+    ] {
+        assert!(apply(with_mod(item(src, dest))).is_err(), "{dest:?}");
+    }
+}
+
+#[test]
+fn bad_filter_env() {
+    let mut patch = Patch::default();
+    patch.filters[1].env = N_ENV;
+
+    assert!(apply(patch).is_err());
+}
+
+#[test]
+fn bad_patch_json() {
+    let mut json: serde_json::Value =
+        serde_json::from_str(&Patch::default().to_json().unwrap()).unwrap();
+    json["mod_matrix"] = serde_json::json!([{
+        "src": { "Lfo": 99 },
+        "dest": { "Osc": { "osc": 99, "param": "Level" } },
+        "amt": 1.0,
+        "bipolar": false,
+    }]);
+    let patch: Patch = serde_json::from_value(json).unwrap();
+
+    assert!(apply(patch).is_err());
+}
+
+#[test]
+fn old_semitone_detune_is_read_as_cents() {
+    let mut json = serde_json::to_value(Patch::default()).unwrap();
+    let osc = json["oscs"][0].as_object_mut().unwrap();
+    osc.remove("cents");
+    osc.insert("detune".into(), serde_json::json!(0.1));
+    let patch: Patch = serde_json::from_value(json).unwrap();
+
+    let cents = with_stack(|| {
+        let mut app = App::default();
+        patch.apply(&mut app).unwrap();
+
+        app.voices[0].oscs[0].0.cents
+    });
+
+    assert!((cents - 10.0).abs() < 1e-4, "{cents}");
+    assert!(!Patch::default().to_json().unwrap().contains("detune"));
+}
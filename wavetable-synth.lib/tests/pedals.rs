@@ -0,0 +1,195 @@
+// sustain & sostenuto pedal tests. each script is a list of MIDI events, after every event the
+// notes that are still sounding, (not released) are checked.
+#![cfg(feature = "desktop")]
+
+use midi_control::MidiMessage;
+use wavetable_synth::{pedal::Pedals, App, MidiControlled};
+
+/// `App` is too big for a test thread's stack in unoptimized builds, (see `golden_audio.rs`).
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug)]
+enum Event {
+    On(u8),
+    Off(u8),
+    Sustain(bool),
+    Sostenuto(bool),
+}
+
+impl Event {
+    fn bytes(self) -> [u8; 3] {
+        let pedal = |down: bool| if down { 127 } else { 0 };
+
+        match self {
+            Self::On(note) => [0x90, note, 100],
+            Self::Off(note) => [0x80, note, 0],
+            Self::Sustain(down) => [0xB0, 64, pedal(down)],
+            Self::Sostenuto(down) => [0xB0, 66, pedal(down)],
+        }
+    }
+}
+
+/// the notes each voice is playing, lowest first.
+fn sounding(app: &App) -> Vec<u8> {
+    let mut notes: Vec<u8> = app
+        .voices
+        .iter()
+        .filter_map(|voice| voice.playing)
+        .collect();
+    notes.sort();
+
+    notes
+}
+
+/// sends each event to a new `App` as MIDI & checks what's sounding afterwards.
+fn run(script: &[(Event, &[u8])]) {
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || {
+                let mut app = App::default();
+
+                for (i, (event, expected)) in script.iter().enumerate() {
+                    app.midi_input(&MidiMessage::from(&event.bytes()[..]));
+
+                    assert_eq!(sounding(&app), *expected, "step {i}, after {event:?}");
+                }
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    });
+}
+
+#[test]
+fn no_pedal_releases_on_note_off() {
+    use Event::*;
+
+    run(&[
+        (On(60), &[60]),
+        (On(64), &[60, 64]),
+        (Off(60), &[64]),
+        (Off(64), &[]),
+    ]);
+}
+
+#[test]
+fn sustain_holds_released_notes_until_lifted() {
+    use Event::*;
+
+    run(&[
+        (On(60), &[60]),
+        (Sustain(true), &[60]),
+        (Off(60), &[60]),
+        (On(64), &[60, 64]),
+        (Off(64), &[60, 64]),
+        (Sustain(false), &[]),
+    ]);
+}
+
+#[test]
+fn sustain_leaves_held_keys_alone() {
+    use Event::*;
+
+    run(&[
+        (Sustain(true), &[]),
+        (On(60), &[60]),
+        (On(64), &[60, 64]),
+        (Off(60), &[60, 64]),
+        (Sustain(false), &[64]),
+        (Off(64), &[]),
+    ]);
+}
+
+#[test]
+fn sustained_note_played_again_reuses_its_voice() {
+    use Event::*;
+
+    run(&[
+        (Sustain(true), &[]),
+        (On(60), &[60]),
+        (Off(60), &[60]),
+        (On(60), &[60]),
+        (Off(60), &[60]),
+        (On(60), &[60]),
+        (Sustain(false), &[60]),
+        (Off(60), &[]),
+    ]);
+}
+
+#[test]
+fn sostenuto_only_holds_notes_down_when_pressed() {
+    use Event::*;
+
+    run(&[
+        (On(48), &[48]),
+        (Sostenuto(true), &[48]),
+        (On(60), &[48, 60]),
+        (Off(48), &[48, 60]),
+        (Off(60), &[48]),
+        (On(64), &[48, 64]),
+        (Off(64), &[48]),
+        (Sostenuto(false), &[]),
+    ]);
+}
+
+#[test]
+fn sostenuto_lifted_with_key_down_keeps_the_note() {
+    use Event::*;
+
+    run(&[
+        (On(48), &[48]),
+        (Sostenuto(true), &[48]),
+        (Sostenuto(false), &[48]),
+        (Off(48), &[]),
+    ]);
+}
+
+#[test]
+fn sustain_and_sostenuto_together() {
+    use Event::*;
+
+    run(&[
+        (On(48), &[48]),
+        (Sostenuto(true), &[48]),
+        (Off(48), &[48]),
+        (Sustain(true), &[48]),
+        (On(60), &[48, 60]),
+        (Off(60), &[48, 60]),
+        // sustain lets 60 go, sostenuto still has 48.
+        (Sustain(false), &[48]),
+        (Sustain(true), &[48]),
+        // sustain is down again, but 48 was let go before it so it stops here.
+        (Sostenuto(false), &[]),
+    ]);
+}
+
+#[test]
+fn sostenuto_lifted_while_sustain_down() {
+    use Event::*;
+
+    run(&[
+        (On(48), &[48]),
+        (Sostenuto(true), &[48]),
+        (Sustain(true), &[48]),
+        (Off(48), &[48]),
+        // the sustain pedal caught 48 too.
+        (Sostenuto(false), &[48]),
+        (Sustain(false), &[]),
+    ]);
+}
+
+#[test]
+fn sostenuto_held_down_doesnt_catch_new_notes() {
+    let mut pedals = Pedals::new();
+
+    pedals.press(48);
+    assert!(pedals.set_sostenuto(true).is_empty());
+    pedals.press(60);
+    // pedals send a stream of values while they move.
+    assert!(pedals.set_sostenuto(true).is_empty());
+
+    assert!(!pedals.release(48));
+    assert!(pedals.release(60));
+    assert_eq!(pedals.set_sostenuto(false).iter().collect::<Vec<_>>(), [48]);
+}
@@ -0,0 +1,78 @@
+// MIDI file rendering tests. the render should run from the first sample to the last event, plus
+// the tail, so the events at the end are played even without a tail.
+#![cfg(feature = "desktop")]
+
+use midly::{Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+use wavetable_synth::{config::SAMPLE_RATE, render::render_midi, App};
+
+/// `App` is too big for a test thread's stack in unoptimized builds, (see `golden_audio.rs`).
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// ticks per beat, at the default 120 bpm that's 960 ticks a second.
+const TICKS_PER_BEAT: u16 = 480;
+
+fn with_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, f)
+            .unwrap()
+            .join()
+            .unwrap()
+    })
+}
+
+/// a MIDI file that plays middle C from `on` to `off`, (in ticks).
+fn note(on: u32, off: u32) -> Vec<u8> {
+    let midi = |delta: u32, message| TrackEvent {
+        delta: delta.into(),
+        kind: TrackEventKind::Midi {
+            channel: 0.into(),
+            message,
+        },
+    };
+    let track = vec![
+        midi(
+            on,
+            MidiMessage::NoteOn {
+                key: 60.into(),
+                vel: 100.into(),
+            },
+        ),
+        midi(
+            off - on,
+            MidiMessage::NoteOff {
+                key: 60.into(),
+                vel: 0.into(),
+            },
+        ),
+        TrackEvent {
+            delta: 0.into(),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        },
+    ];
+    let smf = Smf {
+        header: Header::new(Format::SingleTrack, Timing::Metrical(TICKS_PER_BEAT.into())),
+        tracks: vec![track],
+    };
+    let mut bytes = Vec::new();
+    smf.write_std(&mut bytes).unwrap();
+
+    bytes
+}
+
+fn render(smf: &[u8], tail: f32) -> Vec<f32> {
+    with_stack(|| render_midi(&mut App::default(), smf, tail).unwrap())
+}
+
+#[test]
+fn renders_through_the_last_event() {
+    // the note off is half a second in.
+    let last = SAMPLE_RATE as usize / 2;
+
+    assert_eq!(render(&note(0, TICKS_PER_BEAT as u32), 0.0).len(), last + 1);
+    assert_eq!(
+        render(&note(0, TICKS_PER_BEAT as u32), 0.5).len(),
+        last + 1 + SAMPLE_RATE as usize / 2
+    );
+}
@@ -0,0 +1,133 @@
+// audio sink tests. the sinks should hand back exactly what was written to them, & the runners
+// should pull exactly as much audio as they're asked for.
+#![cfg(feature = "desktop")]
+
+use anyhow::Result;
+use wavetable_synth::{
+    config::SAMPLE_RATE,
+    midi::ShutdownHandle,
+    sink::{run_for, run_until, AudioSink, BufferSink, WavSink, BLOCK_SIZE},
+    App,
+};
+
+/// `App` is too big for a test thread's stack in unoptimized builds, (see `golden_audio.rs`).
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+fn with_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, f)
+            .unwrap()
+            .join()
+            .unwrap()
+    })
+}
+
+/// a ramp, so samples that go missing or get swapped around are noticed.
+fn ramp(len: usize) -> Vec<f32> {
+    (0..len).map(|i| i as f32 / len as f32 - 0.5).collect()
+}
+
+#[test]
+fn buffer_sink_round_trip() {
+    let samples = ramp(1000);
+    let mut sink = BufferSink::default();
+
+    for block in samples.chunks(BLOCK_SIZE) {
+        sink.write(block).unwrap();
+    }
+    sink.finish().unwrap();
+
+    assert_eq!(sink.samples, samples);
+}
+
+#[test]
+fn wav_sink_header_and_length() {
+    let path = std::env::temp_dir().join(format!("wav-sink-{}.wav", std::process::id()));
+    let samples = ramp(1000);
+    let mut sink = WavSink::create(&path).unwrap();
+
+    for block in samples.chunks(300) {
+        sink.write(block).unwrap();
+    }
+    sink.finish().unwrap();
+    assert!(sink.write(&samples).is_err());
+
+    let mut reader = hound::WavReader::open(&path).unwrap();
+    let spec = reader.spec();
+    let written: Vec<f32> = reader.samples::<f32>().map(|s| s.unwrap()).collect();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(spec.channels, 1);
+    assert_eq!(spec.sample_rate, SAMPLE_RATE);
+    assert_eq!(spec.bits_per_sample, 32);
+    assert_eq!(spec.sample_format, hound::SampleFormat::Float);
+    assert_eq!(reader.duration() as usize, samples.len());
+    assert_eq!(written, samples);
+}
+
+#[test]
+fn run_for_pulls_exactly_n_samples() {
+    let lens = with_stack(|| {
+        let mut app = App::default();
+
+        [0, 1, BLOCK_SIZE, BLOCK_SIZE * 3 + 7].map(|n| {
+            let mut sink = BufferSink::default();
+            run_for(&mut app, &mut sink, n).unwrap();
+
+            (n, sink.samples.len())
+        })
+    });
+
+    for (asked, got) in lens {
+        assert_eq!(asked, got);
+    }
+}
+
+/// shuts the runner down after `blocks` blocks.
+struct StopAfter {
+    blocks: usize,
+    written: usize,
+    finished: bool,
+    shutdown: ShutdownHandle,
+}
+
+impl AudioSink for StopAfter {
+    fn write(&mut self, block: &[f32]) -> Result<()> {
+        assert_eq!(block.len(), BLOCK_SIZE);
+        self.written += 1;
+
+        if self.written == self.blocks {
+            self.shutdown.shutdown();
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.finished = true;
+
+        Ok(())
+    }
+}
+
+#[test]
+fn run_until_stops_on_shutdown() {
+    let sink = with_stack(|| {
+        let mut app = App::default();
+        let shutdown = ShutdownHandle::default();
+        let mut sink = StopAfter {
+            blocks: 3,
+            written: 0,
+            finished: false,
+            shutdown: shutdown.clone(),
+        };
+        run_until(&mut app, &mut sink, &shutdown).unwrap();
+
+        sink
+    });
+
+    assert_eq!(sink.written, 3);
+    assert!(sink.finished);
+}
@@ -0,0 +1,112 @@
+// step sequencer tests. at 240 bpm a quarter note step is exactly a quarter of a second, so the
+// samples in a step can be counted out exactly.
+#![cfg(feature = "desktop")]
+
+use wavetable_synth::{
+    arp::NoteDivision,
+    config::SAMPLE_RATE,
+    step_seq::{Step, StepSequencer, MAX_STEPS},
+};
+
+/// how many samples each step lasts.
+const STEP_LEN: usize = SAMPLE_RATE as usize / 4;
+
+/// a sequence of `values` with no glide, playing quarter notes at 240 bpm.
+fn sequence(values: &[f32]) -> StepSequencer {
+    let mut seq = StepSequencer::default();
+    seq.rate = NoteDivision::Quarter;
+    seq.bpm = 240.0;
+    seq.length = values.len();
+
+    for (step, value) in seq.steps.iter_mut().zip(values) {
+        step.value = *value;
+    }
+
+    seq.press();
+
+    seq
+}
+
+/// the samples the sequence plays for its next `n` steps.
+fn steps(seq: &mut StepSequencer, n: usize) -> Vec<Vec<f32>> {
+    (0..n)
+        .map(|_| (0..STEP_LEN).map(|_| seq.get_sample()).collect())
+        .collect()
+}
+
+#[test]
+fn wraps_at_length() {
+    let mut seq = sequence(&[0.1, 0.2, 0.3]);
+    // past `length`, so it shouldn't play.
+    seq.steps[3].value = 0.9;
+
+    let played: Vec<f32> = steps(&mut seq, 7)
+        .iter()
+        .map(|step| {
+            assert!(step.iter().all(|sample| *sample == step[0]));
+
+            step[0]
+        })
+        .collect();
+
+    assert_eq!(played, [0.1, 0.2, 0.3, 0.1, 0.2, 0.3, 0.1]);
+}
+
+#[test]
+fn length_is_clamped() {
+    let mut seq = sequence(&[0.5]);
+    seq.length = 0;
+    assert!(steps(&mut seq, 3)
+        .iter()
+        .flatten()
+        .all(|sample| *sample == 0.5));
+
+    let mut seq = sequence(&[0.5; MAX_STEPS]);
+    seq.length = MAX_STEPS + 10;
+    assert!(steps(&mut seq, MAX_STEPS + 3)
+        .iter()
+        .flatten()
+        .all(|sample| *sample == 0.5));
+}
+
+#[test]
+fn glides_from_the_last_step() {
+    let mut seq = sequence(&[0.0, 1.0, 0.5]);
+    seq.steps[1].glide = 0.5;
+    let played = steps(&mut seq, 3);
+
+    // half way through the glide, (a quarter of the step).
+    assert!((played[1][STEP_LEN / 4] - 0.5).abs() < 1e-4);
+    // slides the whole way up, then holds.
+    assert!(played[1].windows(2).all(|pair| pair[0] <= pair[1]));
+    assert!(played[1][STEP_LEN / 2..]
+        .iter()
+        .all(|sample| *sample == 1.0));
+    // no glide, so straight down to the next step.
+    assert!(played[2].iter().all(|sample| *sample == 0.5));
+}
+
+#[test]
+fn glide_starts_from_the_first_step_on_press() {
+    let mut seq = sequence(&[0.8, 0.2]);
+    seq.steps[0].glide = 1.0;
+    let first = steps(&mut seq, 1).remove(0);
+
+    // nothing to glide from on the first step, so it's held.
+    assert!(first.iter().all(|sample| *sample == 0.8));
+
+    // pressing again starts over from the first step, without gliding from where it was.
+    seq.press();
+    assert!(steps(&mut seq, 1)[0].iter().all(|sample| *sample == 0.8));
+}
+
+#[test]
+fn silent_until_pressed() {
+    let mut seq = StepSequencer::default();
+    seq.steps = [Step {
+        value: 1.0,
+        glide: 0.0,
+    }; MAX_STEPS];
+
+    assert!((0..1000).all(|_| seq.get_sample() == 0.0));
+}
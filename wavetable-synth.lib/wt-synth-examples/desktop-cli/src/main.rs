@@ -1,109 +1,217 @@
-use log::error;
-use std::{
-    sync::{Arc, RwLock},
-    // thread::spawn,
-};
+use anyhow::{Result, anyhow, bail};
+use clap::{Parser, Subcommand};
+use log::*;
+use std::path::{Path, PathBuf};
 use tinyaudio::{OutputDeviceParameters, run_output_device};
 use wavetable_synth::{
     App, SampleGen,
-    common::{
-        LfoParam, LowPass, LowPassParam, ModMatrixDest, ModMatrixItem, ModMatrixSrc, OscParam,
-    },
-    config::SAMPLE_RATE,
-    logger_init,
-    synth_engines::synth::{build_sine_table, osc::N_OVERTONES},
-    voice::Voice,
+    config::{N_OSC, SAMPLE_RATE},
+    list_midi_ports, logger_init,
+    midi::MidiInputs,
+    patch::Patch,
+    render::render_midi_file,
+    tuning::{A4_FREQ, KeyboardMap, Scale, Tuning},
+    wavetable::load_wav,
 };
 
-fn main() -> anyhow::Result<()> {
+/// how long to keep rendering after the last MIDI event, in seconds.
+const TAIL: f32 = 2.0;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "a wavetable synthesizer")]
+struct Cli {
+    /// a patch file to start from, (as saved by `dump-patch`).
+    #[arg(short, long, global = true)]
+    patch: Option<PathBuf>,
+    /// a wave table to load, either a single cycle WAV file or a text file holding a formula.
+    #[arg(short, long, global = true)]
+    wave_table: Option<PathBuf>,
+    /// which oscillator the wave table is loaded into.
+    #[arg(long, global = true, default_value_t = 0)]
+    osc: usize,
+    /// a Scala scale, (.scl) to tune to.
+    #[arg(long, global = true)]
+    scl: Option<PathBuf>,
+    /// a Scala keyboard map, (.kbm) saying which keys play which notes of the scale.
+    #[arg(long, global = true)]
+    kbm: Option<PathBuf>,
+    /// the frequency of A4 in Hz, (a keyboard map sets its own).
+    #[arg(long, global = true, default_value_t = A4_FREQ, conflicts_with = "kbm")]
+    a4: f32,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// plays live from the MIDI inputs until Ctrl-C is pressed.
+    Play {
+        /// only use MIDI ports whose name contains this, (can be given more than once).
+        #[arg(short, long)]
+        include: Vec<String>,
+        /// never use MIDI ports whose name contains this, (can be given more than once).
+        #[arg(short, long)]
+        exclude: Vec<String>,
+        /// only listen to this MIDI channel, 1-16, (can be given more than once).
+        #[arg(short, long, value_parser = clap::value_parser!(u8).range(1..=16))]
+        channel: Vec<u8>,
+    },
+    /// lists the MIDI input ports.
+    Ports,
+    /// renders a standard MIDI file to a WAV file.
+    Render {
+        midi: PathBuf,
+        wav: PathBuf,
+        /// how long to keep rendering after the last MIDI event, in seconds.
+        #[arg(long, default_value_t = TAIL)]
+        tail: f32,
+    },
+    /// prints the patch as JSON, or saves it to `out`.
+    DumpPatch { out: Option<PathBuf> },
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
     logger_init()?;
 
-    // let app = Arc::new(Mutex::new(App::default()));
-    // app.press();
-    let voice = {
-        let mut overtones = [0.0; N_OVERTONES];
-        (0..N_OVERTONES).for_each(|i| overtones[i] = (i + 1) as f64);
-        let wave_table = build_sine_table(&overtones);
-        let mut voice = Voice::new(wave_table);
-        voice.press(48, 60);
+    if let Command::Ports = cli.command {
+        return ports();
+    }
+
+    let mut app = App::default();
+    let patch = load(&cli, &mut app)?;
+
+    match cli.command {
+        Command::Play {
+            include,
+            exclude,
+            channel,
+        } => {
+            let inputs = MidiInputs {
+                include,
+                exclude,
+                channels: if channel.is_empty() {
+                    MidiInputs::default().channels
+                } else {
+                    channel.iter().fold(0, |mask, ch| mask | 1 << (ch - 1))
+                },
+                ..Default::default()
+            };
+
+            play(app, &inputs)
+        }
+        Command::Ports => ports(),
+        Command::Render { midi, wav, tail } => {
+            info!("rendering {} to {}", midi.display(), wav.display());
 
-        Arc::new(RwLock::new(voice))
+            render_midi_file(&mut app, midi, wav, tail)
+        }
+        Command::DumpPatch { out: Some(out) } => patch.save(out),
+        Command::DumpPatch { out: None } => {
+            println!("{}", patch.to_json()?);
+
+            Ok(())
+        }
+    }
+}
+
+/// sets `app` up from the patch & wave table passed on the command line and returns the patch.
+fn load(cli: &Cli, app: &mut App) -> Result<Patch> {
+    let mut patch = match cli.patch {
+        Some(ref path) => Patch::load(path)?,
+        None => Patch::from_app(app),
     };
-    let mut mod_matrix: [Option<ModMatrixItem>; 256] = [None; 256];
-    mod_matrix[0] = Some(ModMatrixItem {
-        src: ModMatrixSrc::Lfo(0),
-        dest: ModMatrixDest::LowPass {
-            low_pass: LowPass::LP1,
-            param: LowPassParam::Res,
-        },
-        // dest: ModMatrixDest::Env {
-        //     env: 0,
-        //     param: EnvParam::Sus,
-        // },
-        amt: 0.5,
-        bipolar: true,
-    });
-    mod_matrix[1] = Some(ModMatrixItem {
-        src: ModMatrixSrc::Env(1),
-        dest: ModMatrixDest::LowPass {
-            low_pass: LowPass::LP1,
-            param: LowPassParam::Cutoff,
-        },
-        amt: 1.0,
-        bipolar: false,
-    });
-    // mod_matrix[3] = Some(ModMatrixItem {
-    //     src: ModMatrixSrc::Lfo(0),
-    //     dest: ModMatrixDest::Osc {
-    //         osc: 0,
-    //         param: OscParam::Level,
-    //     },
-    //     amt: 0.5,
-    //     bipolar: true,
-    // });
-    // mod_matrix[4] = Some(ModMatrixItem {
-    //     src: ModMatrixSrc::Env(1),
-    //     dest: ModMatrixDest::Lfo {
-    //         lfo: 0,
-    //         param: LfoParam::Speed,
-    //     },
-    //     amt: 1.0,
-    //     bipolar: false,
-    // });
+
+    if cli.osc >= N_OSC {
+        bail!("there are only {N_OSC} oscillators, (0..{N_OSC})");
+    }
+
+    let wav = match cli.wave_table {
+        Some(ref path) if is_wav(path) => Some(load_wav(path)?),
+        Some(ref path) => {
+            let formula = std::fs::read_to_string(path)?.trim().to_string();
+
+            if let Some(osc) = patch.oscs.get_mut(cli.osc) {
+                osc.wave_table = Some(formula);
+            }
+
+            None
+        }
+        None => None,
+    };
+
+    patch.apply(app)?;
+    app.set_tuning(tuning(cli)?);
+
+    // WAV tables can't be saved in a patch so they go straight into the voices.
+    if let Some(wave_table) = wav {
+        for voice in app.voices.iter_mut() {
+            voice.oscs[cli.osc].0.wave_table = wave_table.clone();
+        }
+    }
+
+    Ok(patch)
+}
+
+/// the tuning passed on the command line, (12-TET unless a scale or keyboard map is given).
+fn tuning(cli: &Cli) -> Result<Tuning> {
+    let scale = match cli.scl {
+        Some(ref path) => Scale::load(path)?,
+        None => Scale::equal_temperament(),
+    };
+    let map = match cli.kbm {
+        Some(ref path) => KeyboardMap::load(path)?,
+        None => KeyboardMap::linear(cli.a4),
+    };
+
+    Tuning::new(&scale, &map)
+}
+
+fn is_wav(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
+}
+
+fn ports() -> Result<()> {
+    let ports = list_midi_ports()?;
+
+    if ports.is_empty() {
+        println!("no MIDI inputs found");
+    }
+
+    for (i, port) in ports.iter().enumerate() {
+        println!("{i}: {port}");
+    }
+
+    Ok(())
+}
+
+fn play(mut app: App, inputs: &MidiInputs) -> Result<()> {
+    let shutdown = app.shutdown_handle();
+    let control = app.control_handle();
+    ctrlc::set_handler({
+        let shutdown = shutdown.clone();
+
+        move || shutdown.shutdown()
+    })?;
 
     let params = OutputDeviceParameters {
         channels_count: 1,
         sample_rate: SAMPLE_RATE as usize,
-        // channel_sample_count: 2048,
         channel_sample_count: 1024,
     };
-    let device = run_output_device(params, {
-        // let synth = app.clone();
-        let synth = voice.clone();
-
-        move |data| {
-            for samples in data.chunks_mut(params.channels_count) {
-                // let value = synth.lock().unwrap().get_sample(&mod_matrix);
-                // let value = synth.lock().unwrap().get_sample();
-                // info!("value {value}");
-                if let Ok(mut synth) = synth.write() {
-                    let value = synth.get_sample(&mod_matrix);
-
-                    for sample in samples {
-                        // *sample = value.unwrap();
-                        *sample = value;
-                    }
-                }
-            }
+    // the audio thread owns `app` from here on, everything else goes through `control`. audio
+    // stops when this is dropped.
+    let _device = run_output_device(params, move |data| {
+        for samples in data.chunks_mut(params.channels_count) {
+            samples.fill(app.get_sample());
         }
-    });
-
-    if let Err(e) = device {
-        error!("starting audio playback caused error: {e}");
-    }
+    })
+    .map_err(|e| anyhow!("starting audio playback caused error: {e}"))?;
 
-    // run_midi(app.clone())?;
-    loop {}
+    info!("playing, press Ctrl-C to stop");
+    inputs.run(&control, &shutdown)?;
+    info!("stopping");
 
-    // Ok(())
+    Ok(())
 }
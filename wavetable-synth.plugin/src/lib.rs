@@ -2,15 +2,26 @@ use biquad::*;
 use nih_plug::{log::*, prelude::*};
 use std::sync::{Arc, RwLock};
 use wavetable_synth::{
-    common::ModMatrixDest,
-    config::{N_ENV, N_LFO, N_OSC, POLYPHONY, SAMPLE_RATE},
+    arp::{ArpPattern, Arpeggiator, NoteDivision, MAX_OCTAVES},
+    common::{ModMatrixDest, KEY_CENTER},
+    config::{N_ENV, N_LFO, N_OSC, N_STEP_SEQ, POLYPHONY, SAMPLE_RATE},
+    mono::{NotePriority, NoteStack, VoiceMode},
+    pedal::Pedals,
+    step_seq::MAX_STEPS,
     synth_engines::{
         synth::{
             build_sine_table,
-            osc::{OscTarget, N_OVERTONES},
+            noise::NoiseColor,
+            osc::{CrossMod, OscTarget, PhaseMode, N_OVERTONES},
+            sub_osc::{SubOctave, SubShape},
+        },
+        synth_common::{
+            env::{ATTACK, DECAY, RELEASE},
+            filter::{FilterModel, FilterResponse, FilterRouting},
+            glide::{GlideMode, GlideRate, GLIDE_TIME},
         },
-        synth_common::env::{ATTACK, DECAY, RELEASE},
     },
+    tuning::{Tuning, A4_FREQ},
     voice::Voice,
     ModMatrix,
 };
@@ -29,6 +40,18 @@ pub struct WtSynth {
     pub voices: std::sync::Arc<[std::sync::RwLock<Voice>]>,
     /// all pass filter to avoid clipping
     allpass: biquad::DirectForm1<f32>,
+    /// the voice mode notes are being given out with.
+    voice_mode: VoiceMode,
+    /// the notes held down, only used when not in poly mode.
+    held: NoteStack,
+    /// plays held notes one at a time, synced to the host's tempo.
+    arp: Arpeggiator,
+    /// the sustain & sostenuto pedals.
+    pedals: Pedals,
+    /// what the alternate mod source is set to on the next note.
+    alternate: bool,
+    /// the A4 frequency the voices are tuned to.
+    a4: f32,
 }
 
 #[derive(Params, Debug)]
@@ -42,8 +65,33 @@ struct OscParams {
     pub osc_detune: FloatParam,
     #[id = "Osc Note Offset"]
     pub osc_offset: IntParam,
+    /// where the cycle starts on each note
+    #[id = "Osc Phase Mode"]
+    pub osc_phase_mode: EnumParam<PhaseMode>,
+    /// the start phase in reset mode
+    #[id = "Osc Phase"]
+    pub osc_phase: FloatParam,
     #[id = "Osc Target"]
     pub osc_target: EnumParam<OscTarget>,
+    #[id = "Osc Filter 1 Send"]
+    pub osc_filter_1_send: FloatParam,
+    #[id = "Osc Filter 2 Send"]
+    pub osc_filter_2_send: FloatParam,
+    // cross modulation, a source of 0 means off
+    #[id = "Osc Sync Source"]
+    pub osc_sync_src: IntParam,
+    #[id = "Osc FM Source"]
+    pub osc_fm_src: IntParam,
+    #[id = "Osc FM Index"]
+    pub osc_fm_index: FloatParam,
+    #[id = "Osc PM Source"]
+    pub osc_pm_src: IntParam,
+    #[id = "Osc PM Index"]
+    pub osc_pm_index: FloatParam,
+    #[id = "Osc Ring Source"]
+    pub osc_ring_src: IntParam,
+    #[id = "Osc Ring Amount"]
+    pub osc_ring_amt: FloatParam,
 }
 
 impl OscParams {
@@ -66,16 +114,79 @@ impl OscParams {
                 format!("Osc {i} Detune"),
                 0.0,
                 FloatRange::Linear {
-                    min: -1.0,
-                    max: 1.0,
+                    min: -100.0,
+                    max: 100.0,
                 },
-            ),
+            )
+            .with_unit(" cents"),
             osc_offset: IntParam::new(
                 format!("Osc {i} Note Offset"),
                 0,
                 IntRange::Linear { min: -96, max: 96 },
             ),
+            osc_phase_mode: EnumParam::new(format!("Osc {i} Phase Mode"), PhaseMode::Free),
+            osc_phase: FloatParam::new(
+                format!("Osc {i} Phase"),
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
             osc_target: EnumParam::new(format!("Osc {i} Target"), target),
+            osc_filter_1_send: FloatParam::new(
+                format!("Osc {i} Filter 1 Send"),
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+            osc_filter_2_send: FloatParam::new(
+                format!("Osc {i} Filter 2 Send"),
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+            osc_sync_src: Self::source_param(format!("Osc {i} Sync Source")),
+            osc_fm_src: Self::source_param(format!("Osc {i} FM Source")),
+            osc_fm_index: FloatParam::new(
+                format!("Osc {i} FM Index"),
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 4.0 },
+            ),
+            osc_pm_src: Self::source_param(format!("Osc {i} PM Source")),
+            osc_pm_index: FloatParam::new(
+                format!("Osc {i} PM Index"),
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+            osc_ring_src: Self::source_param(format!("Osc {i} Ring Source")),
+            osc_ring_amt: FloatParam::new(
+                format!("Osc {i} Ring Amount"),
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+        }
+    }
+
+    /// picks which oscilator modulates this one, 0 means off. (only earlier oscilators work)
+    fn source_param(name: String) -> IntParam {
+        IntParam::new(
+            name,
+            0,
+            IntRange::Linear {
+                min: 0,
+                max: N_OSC as i32,
+            },
+        )
+    }
+
+    /// turns the source params into a `CrossMod`.
+    fn cross_mod(&self) -> CrossMod {
+        let source = |param: &IntParam| usize::try_from(param.value() - 1).ok();
+
+        CrossMod {
+            sync: source(&self.osc_sync_src),
+            fm: source(&self.osc_fm_src),
+            fm_index: self.osc_fm_index.value(),
+            pm: source(&self.osc_pm_src),
+            pm_index: self.osc_pm_index.value(),
+            ring: source(&self.osc_ring_src),
+            ring_amt: self.osc_ring_amt.value(),
         }
     }
 }
@@ -125,13 +236,90 @@ impl EnvParams {
     }
 }
 
+#[derive(Params, Debug)]
+struct SubParams {
+    #[id = "Sub Enabled"]
+    pub enable: BoolParam,
+    #[id = "Sub Level"]
+    pub level: FloatParam,
+    #[id = "Sub Octave"]
+    pub octave: EnumParam<SubOctave>,
+    #[id = "Sub Shape"]
+    pub shape: EnumParam<SubShape>,
+    #[id = "Sub Target"]
+    pub target: EnumParam<OscTarget>,
+}
+
+impl Default for SubParams {
+    fn default() -> Self {
+        Self {
+            enable: BoolParam::new("Sub Enabled", false),
+            level: FloatParam::new(
+                "Sub Level",
+                0.5,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 1.0,
+                    factor: FloatRange::gain_skew_factor(0.0, 1.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(1.0)),
+            octave: EnumParam::new("Sub Octave", SubOctave::Down1),
+            shape: EnumParam::new("Sub Shape", SubShape::Sine),
+            target: EnumParam::new("Sub Target", OscTarget::Filter1_2),
+        }
+    }
+}
+
+#[derive(Params, Debug)]
+struct NoiseParams {
+    #[id = "Noise Enabled"]
+    pub enable: BoolParam,
+    #[id = "Noise Level"]
+    pub level: FloatParam,
+    #[id = "Noise Color"]
+    pub color: EnumParam<NoiseColor>,
+    #[id = "Noise Target"]
+    pub target: EnumParam<OscTarget>,
+}
+
+impl Default for NoiseParams {
+    fn default() -> Self {
+        Self {
+            enable: BoolParam::new("Noise Enabled", false),
+            level: FloatParam::new(
+                "Noise Level",
+                0.25,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 1.0,
+                    factor: FloatRange::gain_skew_factor(0.0, 1.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(1.0)),
+            color: EnumParam::new("Noise Color", NoiseColor::White),
+            target: EnumParam::new("Noise Target", OscTarget::Filter1_2),
+        }
+    }
+}
+
 #[derive(Params, Debug)]
 struct FilterParams {
     // filter stuff
     // #[id = "Filter Enabled"]
     // pub enabled: BoolParam,
-    #[id = "Key Track Enabled"]
-    pub key_track: BoolParam,
+    #[id = "Model"]
+    pub model: EnumParam<FilterModel>,
+    #[id = "Response"]
+    pub response: EnumParam<FilterResponse>,
+    #[id = "Key Track"]
+    pub key_track: FloatParam,
+    #[id = "Drive"]
+    pub drive: FloatParam,
+    #[id = "Env"]
+    pub env: IntParam,
+    #[id = "Env Amount"]
+    pub env_amt: FloatParam,
     #[id = "Cutoff"]
     pub cutoff: FloatParam,
     #[id = "Resonance"]
@@ -144,7 +332,34 @@ impl FilterParams {
     fn new(i: usize) -> Self {
         Self {
             // enabled: BoolParam::new(format!("Filter {i} Enabled"), true),
-            key_track: BoolParam::new(format!("Filter {i} Key Tracking"), true),
+            model: EnumParam::new(format!("Filter {i} Model"), FilterModel::Moog),
+            response: EnumParam::new(format!("Filter {i} Response"), FilterResponse::LowPass24),
+            key_track: FloatParam::new(
+                format!("Filter {i} Key Tracking"),
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+            drive: FloatParam::new(
+                format!("Filter {i} Drive"),
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+            env: IntParam::new(
+                format!("Filter {i} Env"),
+                N_ENV.min(2) as i32,
+                IntRange::Linear {
+                    min: 1,
+                    max: N_ENV as i32,
+                },
+            ),
+            env_amt: FloatParam::new(
+                format!("Filter {i} Env Amount"),
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            ),
             cutoff: FloatParam::new(
                 format!("Filter {i} Cutoff"),
                 0.5,
@@ -186,20 +401,180 @@ impl LfoParams {
     }
 }
 
+#[derive(Params)]
+struct StepParams {
+    #[id = "Value"]
+    pub value: FloatParam,
+    #[id = "Glide"]
+    pub glide: FloatParam,
+}
+
+impl StepParams {
+    fn new(seq: usize, i: usize) -> Self {
+        Self {
+            value: FloatParam::new(
+                format!("Seq {seq} Step {i} Value"),
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+            glide: FloatParam::new(
+                format!("Seq {seq} Step {i} Glide"),
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+        }
+    }
+}
+
+#[derive(Params)]
+struct StepSeqParams {
+    #[id = "Rate"]
+    pub rate: EnumParam<NoteDivision>,
+    #[id = "Length"]
+    pub length: IntParam,
+    #[nested(array, group = "Steps")]
+    pub steps: Vec<StepParams>,
+}
+
+impl StepSeqParams {
+    fn new(i: usize) -> Self {
+        Self {
+            rate: EnumParam::new(format!("Seq {i} Rate"), NoteDivision::Sixteenth),
+            length: IntParam::new(
+                format!("Seq {i} Length"),
+                16,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_STEPS as i32,
+                },
+            ),
+            steps: (0..MAX_STEPS)
+                .map(|step| StepParams::new(i, step + 1))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Params)]
+struct GlideParams {
+    #[id = "Glide Mode"]
+    pub mode: EnumParam<GlideMode>,
+    #[id = "Glide Rate"]
+    pub rate: EnumParam<GlideRate>,
+    /// seconds per glide or per octave, (see `GlideRate`)
+    #[id = "Glide Time"]
+    pub time: FloatParam,
+}
+
+impl Default for GlideParams {
+    fn default() -> Self {
+        Self {
+            mode: EnumParam::new("Glide Mode", GlideMode::Off),
+            rate: EnumParam::new("Glide Rate", GlideRate::ConstantTime),
+            time: FloatParam::new(
+                "Glide Time",
+                GLIDE_TIME,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 5.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" s"),
+        }
+    }
+}
+
+#[derive(Params)]
+struct ArpParams {
+    #[id = "Arp Enabled"]
+    pub enable: BoolParam,
+    #[id = "Arp Pattern"]
+    pub pattern: EnumParam<ArpPattern>,
+    #[id = "Arp Octaves"]
+    pub octaves: IntParam,
+    #[id = "Arp Rate"]
+    pub rate: EnumParam<NoteDivision>,
+    #[id = "Arp Gate"]
+    pub gate: FloatParam,
+    #[id = "Arp Swing"]
+    pub swing: FloatParam,
+    #[id = "Arp Latch"]
+    pub latch: BoolParam,
+}
+
+impl Default for ArpParams {
+    fn default() -> Self {
+        Self {
+            enable: BoolParam::new("Arp Enabled", false),
+            pattern: EnumParam::new("Arp Pattern", ArpPattern::Up),
+            octaves: IntParam::new(
+                "Arp Octaves",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_OCTAVES as i32,
+                },
+            ),
+            rate: EnumParam::new("Arp Rate", NoteDivision::Sixteenth),
+            gate: FloatParam::new(
+                "Arp Gate",
+                0.5,
+                FloatRange::Linear {
+                    min: 0.05,
+                    max: 1.0,
+                },
+            ),
+            swing: FloatParam::new("Arp Swing", 0.0, FloatRange::Linear { min: 0.0, max: 0.5 }),
+            latch: BoolParam::new("Arp Latch", false),
+        }
+    }
+}
+
 #[derive(Params)]
 struct WtSynthParams {
+    /// poly, or mono/legato on one voice
+    #[id = "Voice Mode"]
+    pub voice_mode: EnumParam<VoiceMode>,
+    /// which held note plays in mono/legato mode
+    #[id = "Note Priority"]
+    pub note_priority: EnumParam<NotePriority>,
+    /// the note the key track mod source is centered on
+    #[id = "Key Center"]
+    pub key_center: IntParam,
+    /// the frequency of A4, (the voices are tuned to 12-TET around it)
+    #[id = "A4"]
+    pub a4: FloatParam,
+    /// portamento
+    #[nested(group = "Glide")]
+    pub glide: GlideParams,
+    /// the arpeggiator, its rate follows the host's tempo
+    #[nested(group = "Arp")]
+    pub arp: ArpParams,
     /// parameters for eatch Oscilator
     #[nested(array, group = "OSC")]
     /// parameters for Envelope Generators
     pub osc: Vec<OscParams>,
+    /// parameters for the sub oscilator
+    #[nested(group = "Sub")]
+    pub sub: SubParams,
+    /// parameters for the noise source
+    #[nested(group = "Noise")]
+    pub noise: NoiseParams,
     #[nested(array, group = "ENV")]
     pub env: Vec<EnvParams>,
     /// parameters for Filter 1 and 2
     #[nested(array, group = "Filter")]
     pub filter: [FilterParams; 2],
+    /// how filter 1 and 2 are wired together
+    #[id = "Filter Routing"]
+    pub filter_routing: EnumParam<FilterRouting>,
     // params for lfos
     #[nested(array, group = "LFO")]
     pub lfo: Vec<LfoParams>,
+    // step sequencers, (mod sources)
+    #[nested(array, group = "Step Seq")]
+    pub step_seq: Vec<StepSeqParams>,
 }
 
 impl Default for WtSynthParams {
@@ -218,12 +593,35 @@ impl Default for WtSynthParams {
         let env = (0..N_ENV).map(|i| EnvParams::new(i + 1)).collect();
         let filter = [FilterParams::new(1), FilterParams::new(2)];
         let lfo = (0..N_LFO).map(|i| LfoParams::new(i + 1)).collect();
+        let step_seq = (0..N_STEP_SEQ).map(|i| StepSeqParams::new(i + 1)).collect();
 
         Self {
+            voice_mode: EnumParam::new("Voice Mode", VoiceMode::Poly),
+            note_priority: EnumParam::new("Note Priority", NotePriority::Last),
+            key_center: IntParam::new(
+                "Key Center",
+                KEY_CENTER as i32,
+                IntRange::Linear { min: 0, max: 127 },
+            ),
+            a4: FloatParam::new(
+                "A4",
+                A4_FREQ,
+                FloatRange::Linear {
+                    min: 415.0,
+                    max: 466.0,
+                },
+            )
+            .with_unit(" Hz"),
+            glide: GlideParams::default(),
+            arp: ArpParams::default(),
             osc,
+            sub: SubParams::default(),
+            noise: NoiseParams::default(),
             env,
             filter,
+            filter_routing: EnumParam::new("Filter Routing", FilterRouting::Split),
             lfo,
+            step_seq,
         }
     }
 }
@@ -237,7 +635,13 @@ impl Default for WtSynth {
         let wave_table = build_sine_table(&overtones);
 
         let voices: std::sync::Arc<[std::sync::RwLock<Voice>]> = (0..POLYPHONY)
-            .map(|_| RwLock::new(Voice::new(wave_table.clone())))
+            .map(|i| {
+                let mut voice = Voice::new(wave_table.clone());
+                // so the voices don't all draw the same random values.
+                voice.seed_random(i as u32);
+
+                RwLock::new(voice)
+            })
             .collect();
 
         // Cutoff and sampling frequencies
@@ -260,6 +664,12 @@ impl Default for WtSynth {
             midi_table: [None; 256],
             voices,
             allpass,
+            voice_mode: VoiceMode::Poly,
+            held: NoteStack::new(),
+            arp: Arpeggiator::new(),
+            pedals: Pedals::new(),
+            alternate: false,
+            a4: A4_FREQ,
         }
     }
 }
@@ -331,87 +741,46 @@ impl Plugin for WtSynth {
         _aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        while let Some(event) = context.next_event() {
-            // info!("recieved event");
-
-            match event {
-                NoteEvent::NoteOn {
-                    timing: _,
-                    voice_id: _,
-                    channel: _,
-                    note,
-                    velocity,
-                } => {
-                    // info!("playing {note}");
-
-                    for voice in self.voices.iter() {
-                        if let Ok(mut voice) = voice.write() {
-                            if voice.playing.is_none() {
-                                voice.press(note, (velocity * 127.) as u8);
-                                break;
-                            }
-                        }
-                    }
-                }
-                NoteEvent::NoteOff {
-                    timing: _,
-                    voice_id: _,
-                    channel: _,
-                    note,
-                    velocity: _,
-                } => {
-                    for voice in self.voices.iter() {
-                        if let Ok(mut voice) = voice.write() {
-                            if voice.playing.is_some_and(|n| n == note) {
-                                voice.release();
-                            }
-                        }
-                    }
+        // set voice parameters
+        self.set_voice_params();
+
+        if let Some(tempo) = context.transport().tempo {
+            let bpm = tempo as f32;
+            self.arp.bpm = bpm;
+            self.voices.iter().for_each(|voice| {
+                if let Ok(mut voice) = voice.write() {
+                    voice.set_tempo(bpm);
                 }
-                NoteEvent::MidiPitchBend {
-                    timing: _,
-                    channel: _,
-                    value,
-                } => {
-                    // log::info!("bend value: {value}");
+            });
+        }
 
-                    let bend = (value * 2.0) - 1.;
+        // reset memo_params
+        self.memo_params = self.params.clone();
 
-                    // log::info!("bend 1: {bend}");
+        // parameter changes already split the buffer, (see `SAMPLE_ACCURATE_AUTOMATION`) but
+        // note events don't, so they're played on the sample they're timed for.
+        let mut next_event = context.next_event();
 
-                    for voice in self.voices.iter() {
-                        if let Ok(mut voice) = voice.write() {
-                            if voice.playing.is_some() {
-                                voice.oscs.iter_mut().for_each(|(osc, enabled)| {
-                                    // log::info!("bend 2: {bend}");
-                                    if *enabled {
-                                        // log::info!("bend 3: {value} => {bend}");
-                                        osc.bend(bend);
-                                    }
-                                });
-                            }
-                        }
-                    }
+        for (sample_id, channel_samples) in buffer.iter_samples().enumerate() {
+            while let Some(event) = next_event {
+                if event.timing() > sample_id as u32 {
+                    break;
                 }
-                NoteEvent::MidiCC {
-                    timing: _,
-                    channel: _,
-                    cc: _cc,
-                    value: _value,
-                } => {
-                    // log::info!("cc: {cc} => {value}");
-                }
-                _ => {}
+
+                self.handle_event(event);
+                next_event = context.next_event();
             }
-        }
 
-        // set voice parameters
-        self.set_voice_params();
+            let step = self.arp.tick();
 
-        // reset memo_params
-        self.memo_params = self.params.clone();
+            if let Some(note) = step.stop {
+                self.release_note(note);
+            }
+
+            if let Some((note, velocity)) = step.play {
+                self.press_note(note, velocity);
+            }
 
-        for channel_samples in buffer.iter_samples() {
             // Smoothing is optionally built into the parameters themselves
             // let gain = self.params.gain.smoothed.next();
             let value: f32 = self
@@ -428,11 +797,194 @@ impl Plugin for WtSynth {
             }
         }
 
+        // anything timed past the end of the buffer.
+        while let Some(event) = next_event {
+            self.handle_event(event);
+            next_event = context.next_event();
+        }
+
         ProcessStatus::Normal
     }
 }
 
 impl WtSynth {
+    fn handle_event(&mut self, event: NoteEvent<()>) {
+        match event {
+            NoteEvent::NoteOn {
+                timing: _,
+                voice_id: _,
+                channel: _,
+                note,
+                velocity,
+            } => {
+                // info!("playing {note}");
+                let velocity = (velocity * 127.) as u8;
+                self.pedals.press(note);
+
+                if self.arp.enabled() {
+                    self.arp.press(note, velocity);
+                } else {
+                    self.press_note(note, velocity);
+                }
+            }
+            NoteEvent::NoteOff {
+                timing: _,
+                voice_id: _,
+                channel: _,
+                note,
+                velocity,
+            } => {
+                let velocity = (velocity * 127.) as u8;
+
+                for voice in self.voices.iter() {
+                    if let Ok(mut voice) = voice.write() {
+                        if voice.playing == Some(note) {
+                            voice.set_release_velocity(velocity);
+                        }
+                    }
+                }
+
+                // the note keeps going if a pedal is holding it.
+                if self.pedals.release(note) {
+                    self.end_note(note);
+                }
+            }
+            NoteEvent::MidiPitchBend {
+                timing: _,
+                channel: _,
+                value,
+            } => {
+                let bend = (value * 2.0) - 1.;
+
+                // every voice, so a note played after the bend starts out bent.
+                for voice in self.voices.iter() {
+                    if let Ok(mut voice) = voice.write() {
+                        voice.bend(bend);
+                    }
+                }
+            }
+            NoteEvent::MidiCC {
+                timing: _,
+                channel: _,
+                cc: 1,
+                value,
+            } => {
+                for voice in self.voices.iter() {
+                    if let Ok(mut voice) = voice.write() {
+                        voice.set_mod_wheel(value);
+                    }
+                }
+            }
+            NoteEvent::MidiCC {
+                timing: _,
+                channel: _,
+                cc: 64,
+                value,
+            } => {
+                let stop = self.pedals.set_sustain(value >= 0.5);
+                stop.iter().for_each(|note| self.end_note(note));
+            }
+            NoteEvent::MidiCC {
+                timing: _,
+                channel: _,
+                cc: 66,
+                value,
+            } => {
+                let stop = self.pedals.set_sostenuto(value >= 0.5);
+                stop.iter().for_each(|note| self.end_note(note));
+            }
+            NoteEvent::MidiCC {
+                timing: _,
+                channel: _,
+                cc: _cc,
+                value: _value,
+            } => {
+                // log::info!("cc: {cc} => {value}");
+            }
+            _ => {}
+        }
+    }
+
+    /// stops `note` once the pedals are done with it.
+    fn end_note(&mut self, note: u8) {
+        if self.arp.enabled() {
+            self.arp.release(note);
+        } else {
+            self.release_note(note);
+        }
+    }
+
+    /// gives `note` to a voice, (skipping the arpeggiator).
+    fn press_note(&mut self, note: u8, velocity: u8) {
+        match self.voice_mode {
+            VoiceMode::Poly => {
+                let find = |playing: Option<u8>| {
+                    self.voices
+                        .iter()
+                        .find(|voice| voice.read().is_ok_and(|voice| voice.playing == playing))
+                };
+                // a note that's still sounding, (held by a pedal) is played again on its own voice.
+                let voice = find(Some(note)).or_else(|| find(None));
+
+                if let Some(Ok(mut voice)) = voice.map(|voice| voice.write()) {
+                    voice.press(note, velocity);
+                    voice.set_alternate(self.alternate);
+                }
+            }
+            mode => {
+                if let Ok(mut voice) = self.voices[0].write() {
+                    self.held.press(
+                        &mut voice,
+                        note,
+                        velocity,
+                        mode == VoiceMode::Legato,
+                        self.params.note_priority.value(),
+                    );
+                    voice.set_alternate(self.alternate);
+                }
+            }
+        }
+
+        self.alternate = !self.alternate;
+    }
+
+    /// lets go of `note` on the voices, (skipping the arpeggiator).
+    fn release_note(&mut self, note: u8) {
+        match self.voice_mode {
+            VoiceMode::Poly => {
+                for voice in self.voices.iter() {
+                    if let Ok(mut voice) = voice.write() {
+                        if voice.playing.is_some_and(|n| n == note) {
+                            voice.release();
+                        }
+                    }
+                }
+            }
+            mode => {
+                if let Ok(mut voice) = self.voices[0].write() {
+                    self.held.release(
+                        &mut voice,
+                        note,
+                        mode == VoiceMode::Legato,
+                        self.params.note_priority.value(),
+                    );
+                }
+            }
+        }
+    }
+
+    /// lets go of every voice so no notes get stuck when switching modes.
+    fn release_all(&mut self) {
+        self.held.clear();
+        self.voices.iter().for_each(|voice| {
+            if let Ok(mut voice) = voice.write() {
+                if voice.playing.is_some() {
+                    voice.release();
+                }
+            }
+        })
+    }
+
     fn set_voice_params(&mut self) {
         // Oscilator
         self.params
@@ -473,8 +1025,8 @@ impl WtSynth {
 
                     self.voices.iter().for_each(|voice| {
                         if let Ok(mut voice) = voice.write() {
-                            if param != voice.oscs[i].0.detune {
-                                voice.oscs[i].0.detune = param;
+                            if param != voice.oscs[i].0.cents {
+                                voice.oscs[i].0.cents = param;
                             }
                         }
                     })
@@ -493,6 +1045,26 @@ impl WtSynth {
                     })
                 }
 
+                // oscilator phase
+                {
+                    let (mode, phase) = (
+                        osc_params.osc_phase_mode.value(),
+                        osc_params.osc_phase.value(),
+                    );
+
+                    self.voices.iter().for_each(|voice| {
+                        if let Ok(mut voice) = voice.write() {
+                            if mode != voice.oscs[i].0.phase_mode {
+                                voice.oscs[i].0.phase_mode = mode;
+                            }
+
+                            if phase != voice.oscs[i].0.phase {
+                                voice.oscs[i].0.phase = phase;
+                            }
+                        }
+                    })
+                }
+
                 // oscilator target
                 {
                     let param = osc_params.osc_target.value();
@@ -505,8 +1077,164 @@ impl WtSynth {
                         }
                     })
                 }
+
+                // oscilator filter sends
+                {
+                    let param = [
+                        osc_params.osc_filter_1_send.value(),
+                        osc_params.osc_filter_2_send.value(),
+                    ];
+
+                    self.voices.iter().for_each(|voice| {
+                        if let Ok(mut voice) = voice.write() {
+                            if param != voice.oscs[i].0.filter_sends {
+                                voice.oscs[i].0.filter_sends = param;
+                            }
+                        }
+                    })
+                }
+
+                // oscilator cross modulation
+                {
+                    let param = osc_params.cross_mod();
+
+                    self.voices.iter().for_each(|voice| {
+                        if let Ok(mut voice) = voice.write() {
+                            if param != voice.oscs[i].0.cross_mod {
+                                voice.oscs[i].0.cross_mod = param;
+                            }
+                        }
+                    })
+                }
             });
 
+        // Sub oscilator
+        {
+            let sub_params = &self.params.sub;
+
+            // sub enabled
+            {
+                let param = sub_params.enable.value();
+
+                self.voices.iter().for_each(|voice| {
+                    if let Ok(mut voice) = voice.write() {
+                        if param != voice.sub.1 {
+                            voice.sub.1 = param;
+                        }
+                    }
+                })
+            }
+
+            // sub level
+            {
+                let param = sub_params.level.smoothed.next();
+
+                self.voices.iter().for_each(|voice| {
+                    if let Ok(mut voice) = voice.write() {
+                        if param != voice.sub.0.level {
+                            voice.sub.0.level = param;
+                        }
+                    }
+                })
+            }
+
+            // sub octave
+            {
+                let param = sub_params.octave.value();
+
+                self.voices.iter().for_each(|voice| {
+                    if let Ok(mut voice) = voice.write() {
+                        if param != voice.sub.0.octave {
+                            voice.sub.0.octave = param;
+                        }
+                    }
+                })
+            }
+
+            // sub shape
+            {
+                let param = sub_params.shape.value();
+
+                self.voices.iter().for_each(|voice| {
+                    if let Ok(mut voice) = voice.write() {
+                        if param != voice.sub.0.shape {
+                            voice.sub.0.shape = param;
+                        }
+                    }
+                })
+            }
+
+            // sub target
+            {
+                let param = sub_params.target.value();
+
+                self.voices.iter().for_each(|voice| {
+                    if let Ok(mut voice) = voice.write() {
+                        if param != voice.sub.0.target {
+                            voice.sub.0.target = param;
+                        }
+                    }
+                })
+            }
+        }
+
+        // Noise
+        {
+            let noise_params = &self.params.noise;
+
+            // noise enabled
+            {
+                let param = noise_params.enable.value();
+
+                self.voices.iter().for_each(|voice| {
+                    if let Ok(mut voice) = voice.write() {
+                        if param != voice.noise.1 {
+                            voice.noise.1 = param;
+                        }
+                    }
+                })
+            }
+
+            // noise level
+            {
+                let param = noise_params.level.smoothed.next();
+
+                self.voices.iter().for_each(|voice| {
+                    if let Ok(mut voice) = voice.write() {
+                        if param != voice.noise.0.level {
+                            voice.noise.0.level = param;
+                        }
+                    }
+                })
+            }
+
+            // noise color
+            {
+                let param = noise_params.color.value();
+
+                self.voices.iter().for_each(|voice| {
+                    if let Ok(mut voice) = voice.write() {
+                        if param != voice.noise.0.color {
+                            voice.noise.0.color = param;
+                        }
+                    }
+                })
+            }
+
+            // noise target
+            {
+                let param = noise_params.target.value();
+
+                self.voices.iter().for_each(|voice| {
+                    if let Ok(mut voice) = voice.write() {
+                        if param != voice.noise.0.target {
+                            voice.noise.0.target = param;
+                        }
+                    }
+                })
+            }
+        }
+
         // Envelope filter
         self.params
             .env
@@ -571,12 +1299,130 @@ impl WtSynth {
                 }
             });
 
+        // Voice mode, switching releases everything so no notes get stuck
+        {
+            let param = self.params.voice_mode.value();
+
+            if param != self.voice_mode {
+                self.voice_mode = param;
+                self.release_all();
+            }
+        }
+
+        // Arpeggiator, switching it on releases everything for the same reason
+        {
+            let enabled = self.params.arp.enable.value();
+
+            if enabled != self.arp.enabled() {
+                self.arp.set_enabled(enabled);
+
+                if enabled {
+                    self.release_all();
+                }
+            }
+
+            let arp_params = &self.params.arp;
+            self.arp.pattern = arp_params.pattern.value();
+            self.arp.octaves = arp_params.octaves.value() as u8;
+            self.arp.rate = arp_params.rate.value();
+            self.arp.gate = arp_params.gate.value();
+            self.arp.swing = arp_params.swing.value();
+
+            if arp_params.latch.value() != self.arp.latch() {
+                self.arp.set_latch(arp_params.latch.value());
+            }
+        }
+
+        // Glide
+        {
+            let glide_params = &self.params.glide;
+            let (mode, rate, time) = (
+                glide_params.mode.value(),
+                glide_params.rate.value(),
+                glide_params.time.value(),
+            );
+
+            self.voices.iter().for_each(|voice| {
+                if let Ok(mut voice) = voice.write() {
+                    voice.glide.mode = mode;
+                    voice.glide.rate = rate;
+                    voice.glide.time = time;
+                }
+            })
+        }
+
+        // Key center
+        {
+            let param = self.params.key_center.value() as u8;
+
+            self.voices.iter().for_each(|voice| {
+                if let Ok(mut voice) = voice.write() {
+                    voice.key_center = param;
+                }
+            })
+        }
+
+        // Tuning
+        {
+            let param = self.params.a4.value();
+
+            if param != self.a4 {
+                self.a4 = param;
+                let tuning = Tuning::equal(param);
+
+                self.voices.iter().for_each(|voice| {
+                    if let Ok(mut voice) = voice.write() {
+                        voice.set_tuning(tuning);
+                    }
+                })
+            }
+        }
+
+        // Filter routing
+        {
+            let param = self.params.filter_routing.value();
+
+            self.voices.iter().for_each(|voice| {
+                if let Ok(mut voice) = voice.write() {
+                    if param != voice.filter_routing {
+                        voice.filter_routing = param;
+                    }
+                }
+            })
+        }
+
         // Filters
         self.params
             .filter
             .iter()
             .enumerate()
             .for_each(|(i, filter_params)| {
+                // filter model
+                {
+                    let param = filter_params.model.value();
+
+                    self.voices.iter().for_each(|voice| {
+                        if let Ok(mut voice) = voice.write() {
+                            if param != voice.filters[i].model {
+                                voice.filters[i].set_model(param);
+                            }
+                        }
+                    })
+                }
+
+                // filter response
+                {
+                    let param = filter_params.response.value();
+
+                    self.voices.iter().for_each(|voice| {
+                        if let Ok(mut voice) = voice.write() {
+                            if param != voice.filters[i].response {
+                                voice.filters[i].set_response(param);
+                            }
+                        }
+                    })
+                }
+
                 // key tracking
                 {
                     let param = filter_params.key_track.value();
@@ -584,7 +1430,46 @@ impl WtSynth {
                     self.voices.iter().for_each(|voice| {
                         if let Ok(mut voice) = voice.write() {
                             if param != voice.filters[i].key_track {
-                                voice.filters[i].key_track = param;
+                                voice.filters[i].set_key_track(param);
+                            }
+                        }
+                    })
+                }
+
+                // filter drive
+                {
+                    let param = filter_params.drive.value();
+
+                    self.voices.iter().for_each(|voice| {
+                        if let Ok(mut voice) = voice.write() {
+                            if param != voice.filters[i].drive {
+                                voice.filters[i].set_drive(param);
+                            }
+                        }
+                    })
+                }
+
+                // filter envelope
+                {
+                    let param = (filter_params.env.value() - 1) as usize;
+
+                    self.voices.iter().for_each(|voice| {
+                        if let Ok(mut voice) = voice.write() {
+                            if param != voice.filters[i].env {
+                                voice.filters[i].env = param;
+                            }
+                        }
+                    })
+                }
+
+                // filter envelope amount
+                {
+                    let param = filter_params.env_amt.value();
+
+                    self.voices.iter().for_each(|voice| {
+                        if let Ok(mut voice) = voice.write() {
+                            if param != voice.filters[i].env_amt {
+                                voice.filters[i].env_amt = param;
                             }
                         }
                     })
@@ -648,6 +1533,44 @@ impl WtSynth {
                     })
                 }
             });
+
+        self.params
+            .step_seq
+            .iter()
+            .enumerate()
+            .for_each(|(i, seq_params)| {
+                let rate = seq_params.rate.value();
+                let length = seq_params.length.value() as usize;
+
+                self.voices.iter().for_each(|voice| {
+                    if let Ok(mut voice) = voice.write() {
+                        let seq = &mut voice.step_seqs[i];
+
+                        if rate != seq.rate {
+                            seq.rate = rate;
+                        }
+
+                        if length != seq.length {
+                            seq.length = length;
+                        }
+
+                        seq.steps.iter_mut().zip(seq_params.steps.iter()).for_each(
+                            |(step, step_params)| {
+                                let (value, glide) =
+                                    (step_params.value.value(), step_params.glide.value());
+
+                                if value != step.value {
+                                    step.value = value;
+                                }
+
+                                if glide != step.glide {
+                                    step.glide = glide;
+                                }
+                            },
+                        );
+                    }
+                })
+            });
     }
 }
 